@@ -0,0 +1,217 @@
+//! `sformat` formats a template with values from the command line, JSON and TOML files, and
+//! the environment, and writes the result to stdout.
+//!
+//! ```text
+//! sformat 'Hello {name:>10}' --var name=Ferris --json ctx.json
+//! ```
+//!
+//! When a variable is defined more than once, `--var` takes precedence over files, files
+//! take precedence over the environment, and later files take precedence over earlier ones.
+
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+use serde_json::{Map, Value};
+use sformat_dynamic::{compile, CompileError, EnvContext, Layered};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "sformat",
+    version,
+    about = "Formats a template with named variables"
+)]
+struct Cli {
+    /// The template to format, e.g. 'Hello {name:>10}'.
+    template: String,
+
+    /// Defines a variable. A value that is a JSON number, boolean or string is parsed as
+    /// one, and any other value is a string. A dotted NAME like `user.name` sets a field of
+    /// an object. Can be repeated.
+    #[arg(short, long = "var", value_name = "NAME=VALUE", value_parser = parse_var)]
+    vars: Vec<(String, Value)>,
+
+    /// Reads variables from a JSON object. Can be repeated.
+    #[arg(long = "json", value_name = "FILE")]
+    json: Vec<PathBuf>,
+
+    /// Reads variables from a TOML table. Can be repeated.
+    #[arg(long = "toml", value_name = "FILE")]
+    toml: Vec<PathBuf>,
+
+    /// Resolves variables that aren't defined otherwise from the environment.
+    #[arg(long)]
+    env: bool,
+
+    /// Only resolves environment variables that start with PREFIX. Implies --env.
+    #[arg(long, value_name = "PREFIX")]
+    env_prefix: Option<String>,
+
+    /// Only compiles the template, and reports any errors.
+    #[arg(long, conflicts_with = "list_vars")]
+    check: bool,
+
+    /// Prints each variable the template references on its own line.
+    #[arg(long)]
+    list_vars: bool,
+}
+
+/// A variable file, in either format.
+enum VarFile<'a> {
+    Json(&'a Path),
+    Toml(&'a Path),
+}
+
+fn parse_var(var: &str) -> Result<(String, Value), String> {
+    let (name, value) = var
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, found '{}'", var))?;
+
+    // Numbers are parsed so they can be formatted as numbers, e.g. with `{n:03}`.
+    let value = match serde_json::from_str(value) {
+        Ok(value @ (Value::Number(_) | Value::Bool(_) | Value::String(_))) => value,
+        _ => Value::String(value.to_owned()),
+    };
+
+    Ok((name.to_owned(), value))
+}
+
+/// Describes a compile error, with the position of a parse error in `template`.
+fn compile_error(template: &str, err: &CompileError) -> String {
+    let offset = match err.offset(template) {
+        Some(offset) => offset,
+        None => return err.to_string(),
+    };
+    let fragment: String = template[offset..].chars().take(16).collect();
+
+    format!("invalid template at byte {} near {:?}", offset, fragment)
+}
+
+/// Returns the `--json` and `--toml` files in the order they were given on the command line.
+fn var_files<'a>(cli: &'a Cli, matches: &ArgMatches) -> Vec<VarFile<'a>> {
+    let json = matches.indices_of("json").into_iter().flatten();
+    let toml = matches.indices_of("toml").into_iter().flatten();
+    let mut files: Vec<_> = json
+        .zip(cli.json.iter().map(|path| VarFile::Json(path)))
+        .chain(toml.zip(cli.toml.iter().map(|path| VarFile::Toml(path))))
+        .collect();
+
+    files.sort_by_key(|(index, _)| *index);
+    files.into_iter().map(|(_, file)| file).collect()
+}
+
+/// Reads a variable file into a map of top-level keys.
+fn read_var_file(file: &VarFile<'_>) -> Result<Map<String, Value>, String> {
+    let path = match file {
+        VarFile::Json(path) | VarFile::Toml(path) => path,
+    };
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("error reading {}: {}", path.display(), err))?;
+    let value = match file {
+        VarFile::Json(_) => serde_json::from_str(&contents).map_err(|err| err.to_string()),
+        VarFile::Toml(_) => toml::from_str(&contents).map_err(|err| err.to_string()),
+    };
+
+    match value {
+        Ok(Value::Object(map)) => Ok(map),
+        Ok(_) => Err(format!("{} doesn't contain an object", path.display())),
+        Err(err) => Err(format!("error parsing {}: {}", path.display(), err)),
+    }
+}
+
+/// Merges the variables from files and `--var` flags, later definitions taking precedence.
+fn variables(cli: &Cli, files: &[VarFile<'_>]) -> Result<Map<String, Value>, String> {
+    let mut variables = Map::new();
+
+    for file in files {
+        variables.extend(read_var_file(file)?);
+    }
+
+    for (name, value) in &cli.vars {
+        insert_var(&mut variables, name, value.clone());
+    }
+
+    Ok(variables)
+}
+
+/// Inserts a `--var`, following a dotted name into nested objects so that `{user.name}`
+/// finds it. Objects are created as needed, and replace any other value in the way.
+fn insert_var(variables: &mut Map<String, Value>, name: &str, value: Value) {
+    let Some((key, rest)) = name.split_once('.') else {
+        variables.insert(name.to_owned(), value);
+        return;
+    };
+
+    let entry = variables.entry(key).or_insert(Value::Null);
+
+    if !entry.is_object() {
+        *entry = Value::Object(Map::new());
+    }
+
+    if let Value::Object(fields) = entry {
+        insert_var(fields, rest, value);
+    }
+}
+
+fn run(cli: &Cli, matches: &ArgMatches) -> Result<(), String> {
+    let format = compile(&cli.template).map_err(|err| compile_error(&cli.template, &err))?;
+
+    if cli.check {
+        return Ok(());
+    }
+
+    let mut stdout = io::stdout().lock();
+
+    if cli.list_vars {
+        let mut seen = Vec::new();
+
+        for name in format.variables() {
+            if !seen.contains(&name) {
+                seen.push(name);
+                writeln!(stdout, "{}", name).map_err(|err| err.to_string())?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    let variables = Value::Object(variables(cli, &var_files(cli, matches))?);
+    let env = match (&cli.env_prefix, cli.env) {
+        (Some(prefix), _) => Some(EnvContext::new().with_prefix(prefix)),
+        (None, true) => Some(EnvContext::new()),
+        (None, false) => None,
+    };
+
+    // Formatted to a string first, so nothing is written if a variable is missing.
+    let formatted = match &env {
+        Some(env) => {
+            let context = Layered(&variables, env);
+
+            format.format_str(&context).map_err(|err| err.to_string())?
+        }
+        None => format
+            .format_str(&variables)
+            .map_err(|err| err.to_string())?,
+    };
+
+    writeln!(stdout, "{}", formatted).map_err(|err| err.to_string())
+}
+
+fn main() -> ExitCode {
+    let matches = Cli::command().get_matches();
+    let cli = match Cli::from_arg_matches(&matches) {
+        Ok(cli) => cli,
+        Err(err) => err.exit(),
+    };
+
+    match run(&cli, &matches) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("sformat: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    fs,
+    process::{Command, Output},
+};
+
+fn sformat(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sformat"))
+        .args(args)
+        .env("SFORMAT_TEST_USER", "ferris")
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+#[test]
+fn test_formats_with_vars_and_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let json = dir.path().join("ctx.json");
+    let toml = dir.path().join("ctx.toml");
+
+    fs::write(&json, r#"{"name": "json", "age": 7, "ratio": 0.5}"#).unwrap();
+    fs::write(&toml, "age = 8\nteam = \"rust\"\n").unwrap();
+
+    let output = sformat(&[
+        "{name:>8}|{age:03}|{ratio:.2}|{team}",
+        "--toml",
+        toml.to_str().unwrap(),
+        "--json",
+        json.to_str().unwrap(),
+        "--var",
+        "name=Ferris",
+    ]);
+
+    // `--var` wins over files, and the JSON file wins over the earlier TOML file.
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "  Ferris|007|0.50|rust\n");
+}
+
+#[test]
+fn test_formats_with_env() {
+    let output = sformat(&["{SFORMAT_TEST_USER}", "--env-prefix", "SFORMAT_"]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "ferris\n");
+
+    let output = sformat(&["{SFORMAT_TEST_USER}"]);
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_check() {
+    let output = sformat(&["{name:>8}", "--check"]);
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let output = sformat(&["Hello {name:>x} and more text", "--check"]);
+
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "sformat: invalid template at byte 6 near \"{name:>x} and mo\"\n"
+    );
+
+    let output = sformat(&["{name:99999999}", "--check"]);
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "sformat: width of variable (name) exceeds the limit of 65535\n"
+    );
+}
+
+#[test]
+fn test_var_values_are_json_scalars() {
+    let output = sformat(&[
+        "{n:03}|{f:.1}|{b}|{s}|{q}|{o}",
+        "--var",
+        "n=5",
+        "--var",
+        "f=-1.25",
+        "--var",
+        "b=true",
+        "--var",
+        "s=05 apples",
+        "--var",
+        "q=\"42\"",
+        "--var",
+        "o={\"a\": 1}",
+    ]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "005|-1.2|true|05 apples|42|{\"a\": 1}\n");
+}
+
+#[test]
+fn test_dotted_vars_set_nested_fields() {
+    let output = sformat(&["{user.name}", "--var", "user.name=Ferris"]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "Ferris\n");
+
+    let dir = tempfile::tempdir().unwrap();
+    let json = dir.path().join("ctx.json");
+
+    fs::write(
+        &json,
+        r#"{"user": {"name": "json", "id": 7}, "team": "rust"}"#,
+    )
+    .unwrap();
+
+    // Only the field is overridden, and the rest of the object is kept.
+    let output = sformat(&[
+        "{user.name}|{user.id}|{team.lead}",
+        "--json",
+        json.to_str().unwrap(),
+        "--var",
+        "user.name=Ferris",
+        "--var",
+        "team.lead=Corro",
+    ]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "Ferris|7|Corro\n");
+}
+
+#[test]
+fn test_list_vars() {
+    let output = sformat(&["{b} {a:>4} {b} {{", "--list-vars"]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "b\na\n");
+}
@@ -6,62 +6,98 @@ use quote::quote;
 use syn::{
     parse_macro_input,
     token::{Comma, FatArrow},
-    Arm, Data, DataStruct, DeriveInput, Expr, Field, Fields, Pat, Path, Type, TypePath,
+    Arm, Data, DataStruct, DeriveInput, Expr, Field, Fields, Ident, Pat, Path, Type, TypePath,
 };
 
-fn get_match_arm(field: &Field) -> Arm {
+/// Returns true if the field is annotated with `#[context(flatten)]`.
+fn is_flattened(field: &Field) -> bool {
+    field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("context"))
+        .any(|attr| match attr.parse_args::<Ident>() {
+            Ok(ident) if ident == "flatten" => true,
+            _ => panic!("unknown context attribute, expected `#[context(flatten)]`"),
+        })
+}
+
+/// Returns an expression that converts the field to a `TypedValue`.
+fn get_typed_value(field: &Field) -> TokenStream2 {
     let field_ident = field.ident.as_ref().unwrap();
-    let expr = match &field.ty {
-        Type::Reference(ref_type) => {
+
+    match &field.ty {
+        Type::Reference(_) => {
             panic!("not yet implemented")
         }
         Type::Path(TypePath {
             path: Path { segments, .. },
             ..
-        }) => match segments.first() {
+        }) => match segments.last() {
+            Some(segment) if segment.ident == "char" => {
+                quote!(sformat_dynamic::TypedValue::Char(self.#field_ident))
+            }
             Some(segment) if segment.ident == "isize" => {
-                quote!(Ok(sformat_dynamic::TypedValue::Int(self.#field_ident)))
+                quote!(sformat_dynamic::TypedValue::Int(self.#field_ident))
+            }
+            Some(segment) if segment.ident == "i128" => {
+                quote!(sformat_dynamic::TypedValue::Int128(self.#field_ident))
             }
             Some(segment) if segment.ident == "i64" => {
-                quote!(Ok(sformat_dynamic::TypedValue::Int64(self.#field_ident)))
+                quote!(sformat_dynamic::TypedValue::Int64(self.#field_ident))
             }
             Some(segment) if segment.ident == "i32" => {
-                quote!(Ok(sformat_dynamic::TypedValue::Int32(self.#field_ident)))
+                quote!(sformat_dynamic::TypedValue::Int32(self.#field_ident))
             }
             Some(segment) if segment.ident == "i16" => {
-                quote!(Ok(sformat_dynamic::TypedValue::Int16(self.#field_ident)))
+                quote!(sformat_dynamic::TypedValue::Int16(self.#field_ident))
             }
             Some(segment) if segment.ident == "i8" => {
-                quote!(Ok(sformat_dynamic::TypedValue::Int8(self.#field_ident)))
+                quote!(sformat_dynamic::TypedValue::Int8(self.#field_ident))
             }
             Some(segment) if segment.ident == "usize" => {
-                quote!(Ok(sformat_dynamic::TypedValue::Uint(self.#field_ident)))
+                quote!(sformat_dynamic::TypedValue::Uint(self.#field_ident))
+            }
+            Some(segment) if segment.ident == "u128" => {
+                quote!(sformat_dynamic::TypedValue::Uint128(self.#field_ident))
             }
             Some(segment) if segment.ident == "u64" => {
-                quote!(Ok(sformat_dynamic::TypedValue::Uint64(self.#field_ident)))
+                quote!(sformat_dynamic::TypedValue::Uint64(self.#field_ident))
             }
             Some(segment) if segment.ident == "u32" => {
-                quote!(Ok(sformat_dynamic::TypedValue::Uint32(self.#field_ident)))
+                quote!(sformat_dynamic::TypedValue::Uint32(self.#field_ident))
             }
             Some(segment) if segment.ident == "u16" => {
-                quote!(Ok(sformat_dynamic::TypedValue::Uint16(self.#field_ident)))
+                quote!(sformat_dynamic::TypedValue::Uint16(self.#field_ident))
             }
             Some(segment) if segment.ident == "u8" => {
-                quote!(Ok(sformat_dynamic::TypedValue::Uint8(self.#field_ident)))
+                quote!(sformat_dynamic::TypedValue::Uint8(self.#field_ident))
             }
             Some(segment) if segment.ident == "f64" => {
-                quote!(Ok(sformat_dynamic::TypedValue::Float64(self.#field_ident)))
+                quote!(sformat_dynamic::TypedValue::Float64(self.#field_ident))
             }
             Some(segment) if segment.ident == "f32" => {
-                quote!(Ok(sformat_dynamic::TypedValue::Float32(self.#field_ident)))
+                quote!(sformat_dynamic::TypedValue::Float32(self.#field_ident))
             }
             Some(segment) if segment.ident == "bool" => {
-                quote!(Ok(sformat_dynamic::TypedValue::Bool(self.#field_ident)))
+                quote!(sformat_dynamic::TypedValue::Bool(self.#field_ident))
+            }
+            Some(segment) if segment.ident == "Duration" => {
+                quote!(sformat_dynamic::TypedValue::Duration(self.#field_ident))
+            }
+            // `NonZero*` integers use the variant of their underlying integer type.
+            Some(segment) if segment.ident.to_string().starts_with("NonZero") => {
+                quote!(sformat_dynamic::TypedValue::from(self.#field_ident))
             }
             _ => panic!("unhandled segment type"),
         },
         _ => panic!("unhandled field type"),
-    };
+    }
+}
+
+fn get_match_arm(field: &Field) -> Arm {
+    let field_ident = field.ident.as_ref().unwrap();
+    let typed_value = get_typed_value(field);
+    let expr = quote!(Ok(#typed_value));
 
     Arm {
         attrs: vec![],
@@ -80,12 +116,19 @@ fn expand_derive_context(input: DeriveInput) -> TokenStream2 {
     }) = &input.data
     {
         let struct_name = input.ident;
-        let match_arms = fields.named.iter().map(get_match_arm);
+        let (flattened, fields): (Vec<_>, Vec<_>) =
+            fields.named.iter().partition(|field| is_flattened(field));
+        let match_arms = fields.into_iter().map(get_match_arm);
+        let flattened_idents = flattened.iter().map(|field| field.ident.as_ref().unwrap());
+        let flattened_types = flattened.iter().map(|field| &field.ty);
 
         let impl_context = quote! {
-            impl<'ctxt> sformat_dynamic::Context<'ctxt> for #struct_name {
+            impl<'ctxt> sformat_dynamic::Context<'ctxt> for #struct_name
+            where
+                #( #flattened_types: sformat_dynamic::Context<'ctxt>, )*
+            {
                 fn get_variable<'b>(
-                    &self,
+                    &'ctxt self,
                     name: sformat_dynamic::Name<'b>
                 ) -> Result<
                         sformat_dynamic::TypedValue<'ctxt>,
@@ -94,7 +137,17 @@ fn expand_derive_context(input: DeriveInput) -> TokenStream2 {
                 {
                     match name {
                         #( #match_arms )*
-                        _ => Err(sformat_dynamic::FormatError::VariableNameError(name)),
+                        _ => {
+                            // Fall back to flattened fields in declaration order.
+                            #(
+                                match sformat_dynamic::Context::get_variable(&self.#flattened_idents, name) {
+                                    Err(sformat_dynamic::FormatError::VariableNameError(_)) => (),
+                                    result => return result,
+                                }
+                            )*
+
+                            Err(sformat_dynamic::FormatError::VariableNameError(name))
+                        }
                     }
                 }
             }
@@ -106,9 +159,86 @@ fn expand_derive_context(input: DeriveInput) -> TokenStream2 {
     }
 }
 
-#[proc_macro_derive(Context)]
+fn expand_derive_indexed_context(input: DeriveInput) -> TokenStream2 {
+    if let Data::Struct(DataStruct {
+        fields: Fields::Named(fields),
+        ..
+    }) = &input.data
+    {
+        let struct_name = input.ident;
+        let (flattened, fields): (Vec<_>, Vec<_>) =
+            fields.named.iter().partition(|field| is_flattened(field));
+        let len = fields.len();
+        let indices: Vec<_> = (0..len).collect();
+        let idents = fields.iter().map(|field| field.ident.as_ref().unwrap());
+        let typed_values = fields.iter().map(|field| get_typed_value(field));
+        let flattened_idents: Vec<_> = flattened
+            .iter()
+            .map(|field| field.ident.as_ref().unwrap())
+            .collect();
+        let flattened_types: Vec<_> = flattened.iter().map(|field| &field.ty).collect();
+
+        // Slots of flattened fields come after the struct's own fields, in declaration
+        // order, so each flattened field is offset by the length of everything before it.
+        let offsets: Vec<_> = (0..flattened_types.len())
+            .map(|i| {
+                let preceding = &flattened_types[..i];
+
+                quote!(#len #( + <#preceding as sformat_dynamic::IndexedContext>::LEN )*)
+            })
+            .collect();
+
+        quote! {
+            impl sformat_dynamic::IndexedContext for #struct_name {
+                const LEN: usize =
+                    #len #( + <#flattened_types as sformat_dynamic::IndexedContext>::LEN )*;
+
+                fn variable_index(name: sformat_dynamic::Name<'_>) -> Option<usize> {
+                    match name {
+                        #( stringify!(#idents) => Some(#indices), )*
+                        _ => {
+                            #(
+                                if let Some(index) = <#flattened_types as sformat_dynamic::IndexedContext>::variable_index(name) {
+                                    return Some(#offsets + index);
+                                }
+                            )*
+
+                            None
+                        }
+                    }
+                }
+
+                fn get_indexed(&self, index: usize) -> Option<sformat_dynamic::TypedValue<'_>> {
+                    match index {
+                        #( #indices => Some(#typed_values), )*
+                        _ => {
+                            #(
+                                if (#offsets..#offsets + <#flattened_types as sformat_dynamic::IndexedContext>::LEN).contains(&index) {
+                                    return sformat_dynamic::IndexedContext::get_indexed(&self.#flattened_idents, index - #offsets);
+                                }
+                            )*
+
+                            None
+                        }
+                    }
+                }
+            }
+        }
+    } else {
+        panic!("expected struct with named fields")
+    }
+}
+
+#[proc_macro_derive(Context, attributes(context))]
 pub fn derive_context(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
     expand_derive_context(input).into()
 }
+
+#[proc_macro_derive(IndexedContext, attributes(context))]
+pub fn derive_indexed_context(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_derive_indexed_context(input).into()
+}
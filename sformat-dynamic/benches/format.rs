@@ -0,0 +1,112 @@
+//! Benchmarks the formatting loop, and checks that it doesn't allocate.
+//!
+//! Every allocation goes through a counting allocator. Before each benchmark runs, the
+//! format is written once while counting, and the benchmark panics if anything was
+//! allocated.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use sformat_dynamic::{compile, CompiledFormat, TypedValue};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Discards everything written to it, so the output doesn't need a buffer.
+struct Sink;
+
+impl fmt::Write for Sink {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        black_box(s);
+        Ok(())
+    }
+}
+
+fn assert_no_allocations(format: &CompiledFormat<'_>, context: &[(&str, TypedValue<'_>)]) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+
+    format.format_fmt(&mut Sink, context).unwrap();
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+    assert_eq!(
+        allocations, 0,
+        "formatting allocated {} time(s)",
+        allocations
+    );
+}
+
+fn bench_format(c: &mut Criterion) {
+    let cases = [
+        (
+            "integers",
+            "{a} {b:+} {c:>12} {d:08} {e}",
+            [
+                ("a", TypedValue::Int64(-1234567890)),
+                ("b", TypedValue::Uint32(42)),
+                ("c", TypedValue::Int128(i128::MIN)),
+                ("d", TypedValue::Int8(-3)),
+                ("e", TypedValue::Uint128(u128::MAX)),
+            ],
+        ),
+        (
+            "floats",
+            "{a} {b:.3} {c:+10.2} {d:e^12} {e:.1}",
+            [
+                ("a", TypedValue::Float64(0.1 + 0.2)),
+                ("b", TypedValue::Float64(-1234.56789)),
+                ("c", TypedValue::Float32(2.5)),
+                ("d", TypedValue::Float64(f64::NAN)),
+                ("e", TypedValue::Float64(1e15)),
+            ],
+        ),
+        (
+            "mixed",
+            "[{a}] {b:<8} {c} {d:>5} took {e:.2}",
+            [
+                ("a", TypedValue::Str("INFO")),
+                ("b", TypedValue::Char('✓')),
+                ("c", TypedValue::Bool(true)),
+                ("d", TypedValue::Uint16(200)),
+                ("e", TypedValue::Duration(Duration::from_micros(1532))),
+            ],
+        ),
+    ];
+
+    for (name, format_str, context) in cases {
+        let format = compile(format_str).unwrap();
+
+        assert_no_allocations(&format, &context);
+
+        c.bench_function(name, |b| {
+            b.iter(|| format.format_fmt(&mut Sink, black_box(&context[..])))
+        });
+    }
+}
+
+criterion_group!(benches, bench_format);
+criterion_main!(benches);
@@ -1,5 +1,5 @@
 use sformat_dynamic::{derive::Context, CompiledFormat};
-use std::io;
+use std::{io, num::NonZeroU32, time::Duration};
 
 #[derive(Context)]
 struct TestContext {
@@ -7,6 +7,10 @@ struct TestContext {
     signed_int: isize,
     float: f64,
     boolean: bool,
+    id: u128,
+    grade: char,
+    retries: NonZeroU32,
+    elapsed: Duration,
 }
 
 fn main() {
@@ -15,8 +19,12 @@ fn main() {
         signed_int: -128,
         float: -1.3918371,
         boolean: false,
+        id: u128::MAX,
+        grade: 'A',
+        retries: NonZeroU32::new(3).unwrap(),
+        elapsed: Duration::from_millis(1532),
     };
-    let format = "uint = {unsigned_int}, int = {signed_int}, float = {float}, bool = {boolean}\n";
+    let format = "uint = {unsigned_int}, int = {signed_int}, float = {float}, bool = {boolean}, id = {id}, grade = {grade}, retries = {retries:02}, elapsed = {elapsed:.1}\n";
     let format: CompiledFormat<'_> = format.try_into().unwrap();
     let mut output = io::stdout();
 
@@ -0,0 +1,35 @@
+use sformat_dynamic::{derive::Context, CompiledFormat};
+use std::io;
+
+#[derive(Context)]
+struct Common {
+    request_id: u64,
+    verbose: bool,
+}
+
+#[derive(Context)]
+struct RequestContext {
+    status: u16,
+    elapsed: f64,
+    #[context(flatten)]
+    common: Common,
+}
+
+fn main() {
+    let context = RequestContext {
+        status: 200,
+        elapsed: 0.0132,
+        common: Common {
+            request_id: 4096,
+            verbose: true,
+        },
+    };
+    let format =
+        "request = {request_id}, status = {status}, elapsed = {elapsed:.3}, verbose = {verbose}\n";
+    let format: CompiledFormat<'_> = format.try_into().unwrap();
+    let mut output = io::stdout();
+
+    format
+        .format(&mut output, &context)
+        .expect("expected to write formatted string");
+}
@@ -1,5 +1,5 @@
-use sformat_dynamic::{CompiledFormat, DynPointer, TypedValue};
-use std::{collections::HashMap, io};
+use sformat_dynamic::{context, CompiledFormat};
+use std::io;
 
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -11,14 +11,14 @@ fn main() {
     let debug = DebugStruct {
         stuff: "hello world",
     };
-    let context = HashMap::from([
-        ("unsigned_int", TypedValue::Uint(128)),
-        ("signed_int", TypedValue::Int(-128)),
-        ("boolean", TypedValue::Bool(false)),
-        ("string", TypedValue::Str("testing")),
-        ("float", TypedValue::Float64(-391.3198491)),
-        ("debug", TypedValue::Dyn(DynPointer::Debug(&debug))),
-    ]);
+    let context = context! {
+        unsigned_int: 128usize,
+        signed_int: -128isize,
+        boolean: false,
+        string: "testing",
+        float: -391.3198491,
+        debug: ?debug,
+    };
     let format = "uint = {unsigned_int}, int = {signed_int}, bool = {boolean}, string = {string}, float = {float}, debug = {debug}\n";
     let format: CompiledFormat<'_> = format.try_into().unwrap();
     let mut output = io::stdout();
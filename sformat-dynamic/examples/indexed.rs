@@ -0,0 +1,42 @@
+use sformat_dynamic::{compile, derive::IndexedContext};
+use std::io;
+
+#[derive(IndexedContext)]
+struct Common {
+    request_id: u64,
+    verbose: bool,
+}
+
+#[derive(IndexedContext)]
+struct RequestContext {
+    status: u16,
+    elapsed: f64,
+    #[context(flatten)]
+    common: Common,
+}
+
+fn main() {
+    let format =
+        compile("request = {request_id}, status = {status}, elapsed = {elapsed:.3}\n").unwrap();
+
+    // Variable names are resolved once, and every line below is formatted by index.
+    let resolved = format
+        .resolve::<RequestContext>()
+        .expect("expected every variable to be a field");
+    let mut output = io::stdout();
+
+    for request_id in 0..3 {
+        let context = RequestContext {
+            status: 200,
+            elapsed: 0.0132 * request_id as f64,
+            common: Common {
+                request_id,
+                verbose: false,
+            },
+        };
+
+        resolved
+            .format(&mut output, &context)
+            .expect("expected to write formatted string");
+    }
+}
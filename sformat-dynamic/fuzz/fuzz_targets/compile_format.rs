@@ -0,0 +1,30 @@
+//! Compiles arbitrary format strings and formats them with a few values of every kind.
+//! Neither step should ever panic.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sformat_dynamic::{compile, FnContext, TypedValue};
+
+fuzz_target!(|format_str: &str| {
+    let Ok(format) = compile(format_str) else {
+        return;
+    };
+
+    let contexts = [
+        TypedValue::Str("straße"),
+        TypedValue::Char('✓'),
+        TypedValue::Bool(false),
+        TypedValue::Int8(i8::MIN),
+        TypedValue::Int128(i128::MIN),
+        TypedValue::Uint64(0),
+        TypedValue::Float32(f32::NAN),
+        TypedValue::Float64(-0.0),
+    ];
+
+    for value in contexts {
+        // Every variable resolves to the same value.
+        let context = FnContext::new(|_name: &str| Some(value));
+        let _ = format.format_str(&context);
+    }
+});
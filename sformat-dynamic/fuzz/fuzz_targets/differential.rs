@@ -0,0 +1,53 @@
+//! Compares formatted output against `std::format!` for arbitrary specs and values.
+
+#![no_main]
+
+#[path = "../../tests/support/mod.rs"]
+mod support;
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use support::{Value, SPECS};
+
+#[derive(Arbitrary, Debug)]
+enum Input {
+    Str(String),
+    Char(char),
+    Bool(bool),
+    Int8(i8),
+    Int64(i64),
+    Int128(i128),
+    Uint64(u64),
+    Uint128(u128),
+    Float32(f32),
+    Float64(f64),
+}
+
+impl From<Input> for Value {
+    fn from(input: Input) -> Value {
+        match input {
+            Input::Str(value) => Value::Str(value),
+            Input::Char(value) => Value::Char(value),
+            Input::Bool(value) => Value::Bool(value),
+            Input::Int8(value) => Value::Int8(value),
+            Input::Int64(value) => Value::Int64(value),
+            Input::Int128(value) => Value::Int128(value),
+            Input::Uint64(value) => Value::Uint64(value),
+            Input::Uint128(value) => Value::Uint128(value),
+            Input::Float32(value) => Value::Float32(value),
+            Input::Float64(value) => Value::Float64(value),
+        }
+    }
+}
+
+fuzz_target!(|input: (u8, u8, Option<u8>, Input)| {
+    let (spec, width, precision, value) = input;
+    let spec = spec as usize % SPECS.len();
+    let (width, precision) = (width as usize, precision.map(usize::from));
+    let value = Value::from(value);
+
+    assert_eq!(
+        value.dynamic_format(spec, width, precision),
+        value.std_format(spec, width, precision)
+    );
+});
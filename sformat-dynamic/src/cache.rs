@@ -0,0 +1,245 @@
+use crate::{
+    compile::{compile_with_limits, CompileError, OwnedFormat},
+    limits::Limits,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+};
+
+/// Entries of the cache, and the order they were last used in.
+#[derive(Debug, Default)]
+struct Lru {
+    entries: HashMap<Box<str>, (Arc<OwnedFormat>, u64)>,
+    // Keys by the tick they were last used at, least recently used first.
+    order: BTreeMap<u64, Box<str>>,
+    tick: u64,
+}
+
+impl Lru {
+    fn get(&mut self, source: &str) -> Option<Arc<OwnedFormat>> {
+        let (format, last_used) = self.entries.get_mut(source)?;
+        let key = self.order.remove(last_used).unwrap();
+
+        self.tick += 1;
+        *last_used = self.tick;
+        self.order.insert(self.tick, key);
+
+        Some(Arc::clone(format))
+    }
+
+    fn insert(&mut self, format: Arc<OwnedFormat>, capacity: usize) {
+        if let Some((_, last_used)) = self.entries.remove(format.source()) {
+            self.order.remove(&last_used);
+        }
+
+        while self.entries.len() >= capacity {
+            match self.order.pop_first() {
+                Some((_, key)) => self.entries.remove(&key),
+                None => return,
+            };
+        }
+
+        self.tick += 1;
+        self.order.insert(self.tick, Box::from(format.source()));
+        self.entries
+            .insert(Box::from(format.source()), (format, self.tick));
+    }
+}
+
+/// Caches compiled formats by their format string.
+///
+/// The least recently used format is evicted once the cache is full. The cache can be
+/// shared between threads, e.g. behind an `Arc`.
+///
+/// ```rust
+/// use sformat_dynamic::{context, TemplateCache};
+///
+/// let cache = TemplateCache::new(128);
+///
+/// for name in ["Ferris", "Corro"] {
+///     let format = cache.get_or_compile("Hello {name}!").unwrap();
+///     let context = context! { name: name };
+///     let formatted = format.as_compiled().format_str(&context);
+///
+///     assert_eq!(formatted.unwrap(), format!("Hello {}!", name));
+/// }
+///
+/// assert_eq!((cache.hits(), cache.misses()), (1, 1));
+/// ```
+#[derive(Debug)]
+pub struct TemplateCache {
+    capacity: usize,
+    limits: Limits,
+    lru: Mutex<Lru>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl TemplateCache {
+    /// Creates a cache that holds up to `capacity` formats.
+    pub fn new(capacity: usize) -> TemplateCache {
+        TemplateCache {
+            capacity,
+            limits: Limits::new(),
+            lru: Mutex::new(Lru::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Compiles formats with `limits` instead of the default limits.
+    pub fn with_limits(mut self, limits: Limits) -> TemplateCache {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns the compiled format for `source`, compiling it if it isn't cached.
+    ///
+    /// Formats that fail to compile aren't cached.
+    pub fn get_or_compile(&self, source: &str) -> Result<Arc<OwnedFormat>, CompileError> {
+        if let Some(format) = self.lock().get(source) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+
+            return Ok(format);
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // The lock isn't held while compiling, so other threads aren't blocked by it. Two
+        // threads may compile the same format at once, in which case the last one is kept.
+        let format = Arc::new(compile_with_limits(source, self.limits)?.into_owned());
+
+        if self.capacity > 0 {
+            self.lock().insert(Arc::clone(&format), self.capacity);
+        }
+
+        Ok(format)
+    }
+
+    /// Returns the number of lookups that found a cached format.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of lookups that had to compile the format.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of cached formats.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Removes every cached format. The hit and miss counters are kept.
+    pub fn clear(&self) {
+        let mut lru = self.lock();
+
+        lru.entries.clear();
+        lru.order.clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Lru> {
+        // The cache is never left half updated by a panic, so a poisoned lock is still
+        // safe to use.
+        self.lru.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TemplateCache;
+    use crate::{
+        compile::{CompileError, OwnedFormat},
+        context::TypedValue,
+        limits::Limits,
+    };
+    use std::{collections::HashMap, sync::Arc, thread};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_template_cache_is_send_and_sync() {
+        assert_send_sync::<TemplateCache>();
+        assert_send_sync::<OwnedFormat>();
+    }
+
+    #[test]
+    fn test_template_cache_evicts_least_recently_used() {
+        let cache = TemplateCache::new(2);
+
+        cache.get_or_compile("{a}").unwrap();
+        cache.get_or_compile("{b}").unwrap();
+        // Using `{a}` makes `{b}` the least recently used.
+        cache.get_or_compile("{a}").unwrap();
+        cache.get_or_compile("{c}").unwrap();
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!((cache.hits(), cache.misses()), (1, 3));
+
+        cache.get_or_compile("{a}").unwrap();
+        cache.get_or_compile("{c}").unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (3, 3));
+
+        cache.get_or_compile("{b}").unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (3, 4));
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_template_cache_errors_and_limits() {
+        let cache = TemplateCache::new(4).with_limits(Limits::new().with_max_width(4));
+
+        assert!(matches!(
+            cache.get_or_compile("{a:5}"),
+            Err(CompileError::WidthLimitError(..))
+        ));
+        assert!(matches!(
+            cache.get_or_compile("{a"),
+            Err(CompileError::ParseError(_))
+        ));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_template_cache_shared_between_threads() {
+        let cache = Arc::new(TemplateCache::new(8));
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let cache = Arc::clone(&cache);
+
+                thread::spawn(move || {
+                    let context = HashMap::from([("i", TypedValue::Int32(i))]);
+
+                    for _ in 0..100 {
+                        let format = cache.get_or_compile("#{i:03}").unwrap();
+                        let formatted = format.as_compiled().format_str(&context).unwrap();
+
+                        assert_eq!(formatted, format!("#{:03}", i));
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(cache.hits() + cache.misses(), 400);
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{
+    context::{Context, TypedValue},
+    format, Name,
+};
+
+/// Looks up a variable in the first context, and falls back to the second context if
+/// the first one doesn't define it.
+///
+/// Layers can be nested to merge any number of contexts, for example
+/// `Layered(&request, Layered(&tenant, &global))`.
+pub struct Layered<A, B>(pub A, pub B);
+
+impl<'ctxt, A, B> Context<'ctxt> for Layered<A, B>
+where
+    A: Context<'ctxt>,
+    B: Context<'ctxt>,
+{
+    fn get_variable<'b>(
+        &'ctxt self,
+        name: Name<'b>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'b>> {
+        match self.0.get_variable(name) {
+            Err(format::Error::VariableNameError(_)) => self.1.get_variable(name),
+            result => result,
+        }
+    }
+}
+
+/// Resolves variables that start with a prefix from the wrapped context.
+///
+/// The prefix is stripped before the lookup, so `Prefixed("env.", ctxt)` resolves
+/// `{env.HOME}` by looking up `HOME` in `ctxt`. Variables without the prefix are not found.
+pub struct Prefixed<'p, C>(pub &'p str, pub C);
+
+impl<'ctxt, C> Context<'ctxt> for Prefixed<'_, C>
+where
+    C: Context<'ctxt>,
+{
+    fn get_variable<'b>(
+        &'ctxt self,
+        name: Name<'b>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'b>> {
+        let stripped = name
+            .strip_prefix(self.0)
+            .ok_or(format::Error::VariableNameError(name))?;
+
+        // Errors should refer to the variable as it was written in the format string.
+        self.1.get_variable(stripped).map_err(|err| match err {
+            format::Error::VariableNameError(_) => format::Error::VariableNameError(name),
+            format::Error::VariableTypeError(_) => format::Error::VariableTypeError(name),
+            err => err,
+        })
+    }
+}
+
+/// Resolves variables with a closure.
+///
+/// The closure returns `None` if the variable isn't defined.
+pub struct FnContext<F>(pub F);
+
+impl<F> FnContext<F> {
+    /// Wraps a closure. Prefer this over the tuple constructor when passing a closure
+    /// directly, as it helps with type inference of the closure's argument.
+    pub fn new<'ctxt>(resolve: F) -> FnContext<F>
+    where
+        F: Fn(Name<'_>) -> Option<TypedValue<'ctxt>>,
+    {
+        FnContext(resolve)
+    }
+}
+
+impl<'ctxt, F> Context<'ctxt> for FnContext<F>
+where
+    F: Fn(Name<'_>) -> Option<TypedValue<'ctxt>>,
+{
+    fn get_variable<'b>(
+        &'ctxt self,
+        name: Name<'b>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'b>> {
+        (self.0)(name).ok_or(format::Error::VariableNameError(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FnContext, Layered, Prefixed};
+    use crate::{compile, context::TypedValue, format};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_layered_prefers_first_context() {
+        let request = BTreeMap::from([("name", TypedValue::Str("request"))]);
+        let global = BTreeMap::from([
+            ("name", TypedValue::Str("global")),
+            ("region", TypedValue::Str("eu")),
+        ]);
+        let context = Layered(&request, &global);
+        let formatted = compile("{name} in {region}").unwrap().format_str(&context);
+
+        assert_eq!(formatted.unwrap(), "request in eu");
+    }
+
+    #[test]
+    fn test_layered_missing_variable() {
+        let context = Layered(
+            BTreeMap::from([("a", TypedValue::Uint(1))]),
+            BTreeMap::from([("b", TypedValue::Uint(2))]),
+        );
+        let formatted = compile("{c}").unwrap().format_str(&context);
+
+        assert!(matches!(
+            formatted,
+            Err(format::Error::VariableNameError("c"))
+        ));
+    }
+
+    #[test]
+    fn test_prefixed() {
+        let env = BTreeMap::from([("HOME", TypedValue::Str("/home/ferris"))]);
+        let context = Layered(
+            Prefixed("env.", &env),
+            BTreeMap::from([("HOME", TypedValue::Str("unprefixed"))]),
+        );
+        let formatted = compile("{env.HOME}/{HOME}").unwrap().format_str(&context);
+
+        assert_eq!(formatted.unwrap(), "/home/ferris/unprefixed");
+    }
+
+    #[test]
+    fn test_prefixed_missing_variable_keeps_full_name() {
+        let context = Prefixed("env.", BTreeMap::from([("HOME", TypedValue::Str("/"))]));
+        let formatted = compile("{env.USER}").unwrap().format_str(&context);
+
+        assert!(matches!(
+            formatted,
+            Err(format::Error::VariableNameError("env.USER"))
+        ));
+    }
+
+    #[test]
+    fn test_fn_context() {
+        let context = FnContext::new(|name: &str| match name {
+            "len" => Some(TypedValue::Uint(name.len())),
+            _ => None,
+        });
+        let formatted = compile("{len:03}").unwrap().format_str(&context);
+
+        assert_eq!(formatted.unwrap(), "003");
+    }
+}
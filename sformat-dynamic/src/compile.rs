@@ -1,26 +1,92 @@
+#[cfg(feature = "std")]
+use crate::format::IoWriter;
 use crate::{
-    context::Context,
+    context::{Context, TypedValue},
+    escape::Escaper,
     format::{self, Alignment, Fill, Flags, Format, SignFlag},
-    token::Token,
+    indexed::IndexedContext,
+    limits::{LimitedWriter, Limits},
+    parse::{self, Captures},
+    pattern,
+    token::{Node, Token},
+    Name,
 };
+use alloc::{borrow::Cow, boxed::Box, string::String, vec::Vec};
+use core::{fmt, marker::PhantomData};
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_while, take_while1},
     character::complete::{anychar, char, satisfy, u32},
     combinator::{eof, map, opt, recognize, value},
     error::{ErrorKind, ParseError},
-    multi::many_till,
+    multi::{many0, many_till},
     sequence::{delimited, pair, preceded, tuple},
     IResult,
 };
-use std::io::Write;
+#[cfg(feature = "std")]
+use std::io;
 use unicode_xid::UnicodeXID;
 
 /// Error compiling a format string.
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug)]
 pub enum CompileError {
-    #[error("error parsing format string: {0}")]
-    ParseError(#[from] nom::Err<(String, ErrorKind)>),
+    ParseError(nom::Err<(String, ErrorKind)>),
+    WidthLimitError(String, u32),
+    PrecisionLimitError(String, u32),
+    TokenLimitError(usize),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::ParseError(err) => write!(f, "error parsing format string: {}", err),
+            CompileError::WidthLimitError(name, limit) => write!(
+                f,
+                "width of variable ({}) exceeds the limit of {}",
+                name, limit
+            ),
+            CompileError::PrecisionLimitError(name, limit) => write!(
+                f,
+                "precision of variable ({}) exceeds the limit of {}",
+                name, limit
+            ),
+            CompileError::TokenLimitError(limit) => {
+                write!(f, "format string has more than {} tokens", limit)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CompileError {}
+
+impl CompileError {
+    /// Returns the byte offset in `format_str` where parsing failed, for a `ParseError`
+    /// returned when compiling `format_str`.
+    ///
+    /// ```rust
+    /// use sformat_dynamic::compile;
+    ///
+    /// let format_str = "Hello {name:>x}";
+    /// let err = compile(format_str).unwrap_err();
+    ///
+    /// assert_eq!(err.offset(format_str), Some(6));
+    /// ```
+    pub fn offset(&self, format_str: &str) -> Option<usize> {
+        match self {
+            CompileError::ParseError(nom::Err::Error((rest, _)))
+            | CompileError::ParseError(nom::Err::Failure((rest, _))) => {
+                format_str.len().checked_sub(rest.len())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<nom::Err<(String, ErrorKind)>> for CompileError {
+    fn from(err: nom::Err<(String, ErrorKind)>) -> CompileError {
+        CompileError::ParseError(err)
+    }
 }
 
 /// Parses '<', '^', or '>'.
@@ -71,6 +137,23 @@ where
     ))(input)
 }
 
+/// Parses a variable name.
+///
+/// A variable name is a Rust identifier, optionally followed by `.` separated path segments
+/// (e.g. `env.HOME` or `items.0`).
+fn variable_name_parser<'a, Error>(input: &'a str) -> IResult<&'a str, &'a str, Error>
+where
+    Error: ParseError<&'a str>,
+{
+    recognize(pair(
+        rust_identifier_parser,
+        many0(preceded(
+            char('.'),
+            take_while1(|c: char| c.is_xid_continue()),
+        )),
+    ))(input)
+}
+
 /// Parses a format spec.
 ///
 /// Format spec is described here: https://doc.rust-lang.org/std/fmt/
@@ -101,17 +184,33 @@ where
     )(input)
 }
 
-/// Compiles a format string.
+/// Compiles a format string with the default [`Limits`].
 pub fn compile(format_str: &'_ str) -> Result<CompiledFormat<'_>, CompileError> {
+    compile_with_limits(format_str, Limits::new())
+}
+
+/// Compiles a format string, checking it against `limits`.
+///
+/// The output limit is kept with the compiled format, and checked every time it's
+/// formatted.
+pub fn compile_with_limits(
+    format_str: &'_ str,
+    limits: Limits,
+) -> Result<CompiledFormat<'_>, CompileError> {
     let (_all, (tokens, _rest)) = many_till(
         alt((
             // Escape Left Curly Brace
             map(tag("{{"), Token::Literal),
+            // Include
+            map(
+                delimited(tag("{>"), variable_name_parser, char('}')),
+                Token::Include,
+            ),
             // Identifier
             map(
                 delimited(
                     char('{'),
-                    tuple((rust_identifier_parser, opt(format_parser))),
+                    tuple((variable_name_parser, opt(format_parser))),
                     char('}'),
                 ),
                 |(identifier, format)| Token::Variable(identifier, format),
@@ -121,29 +220,181 @@ pub fn compile(format_str: &'_ str) -> Result<CompiledFormat<'_>, CompileError>
         )),
         eof,
     )(format_str)
-    .map_err(nom::Err::<(&str, ErrorKind)>::to_owned)?;
+    .map_err(nom::Err::<(&str, ErrorKind)>::to_owned)
+    .map_err(CompileError::ParseError)?;
+
+    if tokens.len() > limits.max_tokens() {
+        return Err(CompileError::TokenLimitError(limits.max_tokens()));
+    }
+
+    for token in tokens.iter() {
+        if let Token::Variable(name, Some(format)) = token {
+            if format.width().unwrap_or(0) > limits.max_width() {
+                return Err(CompileError::WidthLimitError(
+                    String::from(*name),
+                    limits.max_width(),
+                ));
+            }
 
-    Ok(CompiledFormat { ast: tokens })
+            if format.precision().unwrap_or(0) > limits.max_precision() {
+                return Err(CompileError::PrecisionLimitError(
+                    String::from(*name),
+                    limits.max_precision(),
+                ));
+            }
+        }
+    }
+
+    let ast: Vec<_> = tokens
+        .into_iter()
+        .map(|token| Node::new(format_str, token))
+        .collect();
+
+    Ok(CompiledFormat {
+        source: format_str,
+        literal_len: Node::literal_len(&ast),
+        ast: Cow::Owned(ast),
+        limits,
+    })
 }
 
 #[derive(Debug)]
 pub struct CompiledFormat<'format> {
-    ast: Vec<Token<'format>>,
+    source: &'format str,
+    // Borrowed when the format is lent out by an `OwnedFormat`.
+    ast: Cow<'format, [Node]>,
+    // Total length of the literals, in bytes.
+    literal_len: usize,
+    limits: Limits,
 }
 
 impl<'format> CompiledFormat<'format> {
+    /// Writes the formatted string to an `io::Write`.
+    #[cfg(feature = "std")]
     pub fn format<'ctxt, W, C>(
         &self,
         write: &mut W,
         context: &'ctxt C,
     ) -> Result<(), format::Error<'ctxt>>
     where
-        W: Write,
-        C: Context<'ctxt>,
+        W: io::Write,
+        C: Context<'ctxt> + ?Sized,
         'format: 'ctxt,
     {
-        for token in self.ast.iter() {
-            token.write_token(write, context)?;
+        self.format_io_with(write, None, |_, name| context.get_variable(name))
+    }
+
+    /// Writes the formatted string to an `io::Write`, escaping the output of every variable
+    /// with `escaper`. Literals are written as is.
+    #[cfg(feature = "std")]
+    pub fn format_escaped<'ctxt, W, C>(
+        &self,
+        write: &mut W,
+        context: &'ctxt C,
+        escaper: &dyn Escaper,
+    ) -> Result<(), format::Error<'ctxt>>
+    where
+        W: io::Write,
+        C: Context<'ctxt> + ?Sized,
+        'format: 'ctxt,
+    {
+        self.format_io_with(write, Some(escaper), |_, name| context.get_variable(name))
+    }
+
+    #[cfg(feature = "std")]
+    /// Writes every token, looking up the variable of the token at each index with
+    /// `get_variable`.
+    fn format_io_with<'ctxt, W, F>(
+        &self,
+        write: &mut W,
+        escaper: Option<&dyn Escaper>,
+        mut get_variable: F,
+    ) -> Result<(), format::Error<'ctxt>>
+    where
+        W: io::Write,
+        F: FnMut(usize, Name<'ctxt>) -> Result<TypedValue<'ctxt>, format::Error<'ctxt>>,
+        'format: 'ctxt,
+    {
+        let mut write = IoWriter::new(write);
+        let mut write = LimitedWriter::new(&mut write, self.limits.max_output_bytes());
+
+        for (i, token) in self.tokens().enumerate() {
+            token
+                .write_token(&mut write, |name| get_variable(i, name), escaper)
+                .map_err(|err| {
+                    if write.exceeded {
+                        return format::Error::OutputLimitError(self.limits.max_output_bytes());
+                    }
+
+                    match (write.inner_mut().error.take(), token) {
+                        (Some(err), Token::Literal(_)) => format::Error::WriteLiteralError(err),
+                        (Some(err), Token::Variable(name, _)) => {
+                            format::Error::WriteVariableError(name, err)
+                        }
+                        // Includes never write anything.
+                        (Some(_), Token::Include(_)) | (None, _) => err,
+                    }
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the formatted string to a `fmt::Write`, like a `String` or a `fmt::Formatter`.
+    pub fn format_fmt<'ctxt, W, C>(
+        &self,
+        write: &mut W,
+        context: &'ctxt C,
+    ) -> Result<(), format::Error<'ctxt>>
+    where
+        W: fmt::Write + ?Sized,
+        C: Context<'ctxt> + ?Sized,
+        'format: 'ctxt,
+    {
+        self.format_fmt_with(write, None, |_, name| context.get_variable(name))
+    }
+
+    /// Writes the formatted string to a `fmt::Write`, escaping the output of every variable
+    /// with `escaper`. Literals are written as is.
+    pub fn format_fmt_escaped<'ctxt, W, C>(
+        &self,
+        write: &mut W,
+        context: &'ctxt C,
+        escaper: &dyn Escaper,
+    ) -> Result<(), format::Error<'ctxt>>
+    where
+        W: fmt::Write + ?Sized,
+        C: Context<'ctxt> + ?Sized,
+        'format: 'ctxt,
+    {
+        self.format_fmt_with(write, Some(escaper), |_, name| context.get_variable(name))
+    }
+
+    /// Writes every token, looking up the variable of the token at each index with
+    /// `get_variable`.
+    fn format_fmt_with<'ctxt, W, F>(
+        &self,
+        write: &mut W,
+        escaper: Option<&dyn Escaper>,
+        mut get_variable: F,
+    ) -> Result<(), format::Error<'ctxt>>
+    where
+        W: fmt::Write + ?Sized,
+        F: FnMut(usize, Name<'ctxt>) -> Result<TypedValue<'ctxt>, format::Error<'ctxt>>,
+        'format: 'ctxt,
+    {
+        let mut write = LimitedWriter::new(write, self.limits.max_output_bytes());
+
+        for (i, token) in self.tokens().enumerate() {
+            token
+                .write_token(&mut write, |name| get_variable(i, name), escaper)
+                .map_err(|err| {
+                    if write.exceeded {
+                        format::Error::OutputLimitError(self.limits.max_output_bytes())
+                    } else {
+                        err
+                    }
+                })?;
         }
 
         Ok(())
@@ -151,19 +402,415 @@ impl<'format> CompiledFormat<'format> {
 
     pub fn format_str<'ctxt, C>(&self, context: &'ctxt C) -> Result<String, format::Error<'ctxt>>
     where
-        C: Context<'ctxt>,
+        C: Context<'ctxt> + ?Sized,
+        'format: 'ctxt,
+    {
+        let mut formatted = String::with_capacity(self.literal_len);
+
+        self.format_fmt(&mut formatted, context)?;
+
+        Ok(formatted)
+    }
+
+    /// Appends the formatted string to `buf`, reserving the space it needs up front with
+    /// [`estimate_len`](Self::estimate_len).
+    ///
+    /// Measuring the output looks each variable up twice, so wrap variables that are
+    /// expensive to compute in a [`LazyContext`](crate::LazyContext), whose scope only
+    /// computes them once.
+    ///
+    /// The same buffer can be reused for many calls by clearing it in between, so it only
+    /// grows when a formatted string is longer than any before it.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "std")] {
+    /// use sformat_dynamic::{compile, context};
+    ///
+    /// let format = compile("{name:>8}: {count}\n").unwrap();
+    /// let mut buf = String::new();
+    ///
+    /// for (name, count) in [("Ferris", 7), ("Corro", 12)] {
+    ///     buf.clear();
+    ///     format.format_into(&mut buf, &context! { name: name, count: count }).unwrap();
+    ///     print!("{}", buf);
+    /// }
+    /// # }
+    /// ```
+    pub fn format_into<'ctxt, C>(
+        &self,
+        buf: &mut String,
+        context: &'ctxt C,
+    ) -> Result<(), format::Error<'ctxt>>
+    where
+        C: Context<'ctxt> + ?Sized,
         'format: 'ctxt,
     {
-        let mut formatted = Vec::new();
+        buf.reserve(
+            self.estimate_len(context)
+                .min(self.limits.max_output_bytes()),
+        );
 
-        self.format(&mut formatted, context)?;
+        self.format_fmt(buf, context)
+    }
+
+    /// Returns the total length of the literals in bytes. This is the length of the output
+    /// if every variable is empty.
+    pub fn literal_len(&self) -> usize {
+        self.literal_len
+    }
 
-        Ok(String::from_utf8(formatted).expect("expected utf-8 encoded string"))
+    /// Returns the name of each variable in the order they appear. A variable that is used
+    /// more than once is returned each time.
+    pub fn variables(&self) -> impl Iterator<Item = Name<'format>> + '_ {
+        self.tokens().filter_map(|token| match token {
+            Token::Variable(name, _) => Some(name),
+            Token::Literal(_) | Token::Include(_) => None,
+        })
+    }
+
+    /// Extracts the values of the variables from a string this format produced.
+    ///
+    /// The literals have to match exactly. Each variable extends to the first occurrence
+    /// of the literal that follows it, so a value that contains that literal is cut short.
+    /// For two adjacent variables, the first extends to its width. Padding added for the
+    /// width, fill and zero flag is removed from the values.
+    ///
+    /// Without an explicit alignment, spaces are trimmed from both sides of a value that
+    /// fills its width, since it could have been a left aligned string or a right aligned
+    /// number. So a string with leading spaces doesn't round-trip through e.g. `{x:6}`,
+    /// but does through `{x:<6}`.
+    ///
+    /// ```rust
+    /// use sformat_dynamic::{compile, TypedValue, ValueKind};
+    ///
+    /// let format = compile("{date}_{host}_{seq:05}.log").unwrap();
+    /// let captures = format.parse("2024-01-02_crab-01_00042.log").unwrap();
+    ///
+    /// assert_eq!(captures.get("host"), Some("crab-01"));
+    /// assert!(matches!(
+    ///     captures.get_as("seq", ValueKind::Uint32),
+    ///     Ok(TypedValue::Uint32(42))
+    /// ));
+    /// ```
+    pub fn parse<'s>(
+        &self,
+        input: &'s str,
+    ) -> Result<Captures<'format, 's>, parse::Error<'format>> {
+        parse::parse(self.tokens(), input)
+    }
+
+    /// Converts the format to a regex that matches its output.
+    ///
+    /// Each variable becomes a named capture group, constrained by its width and numeric
+    /// flags. Regex group names have to be unique, so only the first use of a variable is
+    /// captured, and repeated uses aren't checked to be equal. The regex is returned as a
+    /// string, to be compiled with a crate like `regex`.
+    ///
+    /// ```rust
+    /// use sformat_dynamic::compile;
+    ///
+    /// let format = compile("{host}_{seq:05}.log").unwrap();
+    ///
+    /// assert_eq!(
+    ///     format.to_regex().unwrap(),
+    ///     r"^(?P<host>.*?)_(?P<seq>(?:[+-][0-9.]{4,}|[0-9.]{5,}))\.log$"
+    /// );
+    /// ```
+    pub fn to_regex(&self) -> Result<String, parse::Error<'format>> {
+        pattern::to_regex(self.tokens())
+    }
+
+    /// Converts the format to a shell glob that matches its output.
+    ///
+    /// Variables match any characters, at least as many as their width.
+    ///
+    /// ```rust
+    /// use sformat_dynamic::compile;
+    ///
+    /// let format = compile("{host}_{seq:05}.log").unwrap();
+    ///
+    /// assert_eq!(format.to_glob().unwrap(), "*_?????*.log");
+    /// ```
+    pub fn to_glob(&self) -> Result<String, parse::Error<'format>> {
+        pattern::to_glob(self.tokens())
+    }
+
+    /// Returns the length of the formatted output in bytes, including padding.
+    ///
+    /// Each variable is looked up and converted to a string once, and the padding is
+    /// computed from the width rather than written out, so the estimate is exact unless a
+    /// `Debug` or `Display` value formats differently each time. Variables that can't be
+    /// looked up count as empty.
+    pub fn estimate_len<'ctxt, C>(&self, context: &'ctxt C) -> usize
+    where
+        C: Context<'ctxt> + ?Sized,
+        'format: 'ctxt,
+    {
+        self.tokens()
+            .filter_map(|token| match token {
+                // Errors are reported when the format is actually written.
+                Token::Variable(name, format) => {
+                    let val = context.get_variable(name).ok()?;
+
+                    Some(match format {
+                        Some(format) => format.formatted_len(val),
+                        None => val.string_repr(None).as_ref().len(),
+                    })
+                }
+                Token::Literal(_) | Token::Include(_) => None,
+            })
+            .fold(self.literal_len, usize::saturating_add)
+    }
+
+    /// Formats to a `String`, escaping the output of every variable with `escaper`.
+    /// Literals are written as is.
+    pub fn format_str_escaped<'ctxt, C>(
+        &self,
+        context: &'ctxt C,
+        escaper: &dyn Escaper,
+    ) -> Result<String, format::Error<'ctxt>>
+    where
+        C: Context<'ctxt> + ?Sized,
+        'format: 'ctxt,
+    {
+        let mut formatted = String::new();
+
+        self.format_fmt_escaped(&mut formatted, context, escaper)?;
+
+        Ok(formatted)
+    }
+
+    /// Binds a context to the format, returning a value that implements `Display`.
+    ///
+    /// Errors, like a missing variable, can't be reported through `Display`, and are
+    /// turned into a `fmt::Error`. This makes `to_string` panic, so prefer
+    /// [`CompiledFormat::format_str`] unless the context is known to be complete.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "std")] {
+    /// use sformat_dynamic::{compile, context};
+    ///
+    /// let format = compile("{name:>8}").unwrap();
+    /// let context = context! { name: "Ferris" };
+    ///
+    /// assert_eq!(format!("[{}]", format.bind(&context)), "[  Ferris]");
+    /// # }
+    /// ```
+    pub fn bind<'a, 'ctxt, C>(&'a self, context: &'ctxt C) -> BoundFormat<'a, 'format, 'ctxt, C>
+    where
+        C: Context<'ctxt> + ?Sized,
+        'format: 'ctxt,
+    {
+        BoundFormat {
+            format: self,
+            context,
+        }
+    }
+
+    /// Resolves the variables of the format to the indices of an [`IndexedContext`].
+    ///
+    /// The resolved format only compares variable names once, here, and is formatted by
+    /// looking up values by index. A variable that `C` doesn't have is reported now,
+    /// instead of every time the format is used.
+    ///
+    /// ```rust,ignore
+    /// // Note: This example requires the "derive" feature.
+    /// use sformat_dynamic::{compile, derive::IndexedContext};
+    ///
+    /// #[derive(IndexedContext)]
+    /// struct Request {
+    ///   status: u16,
+    ///   bytes: u64,
+    /// }
+    ///
+    /// let format = compile("{status} {bytes}").unwrap();
+    /// let resolved = format.resolve::<Request>().unwrap();
+    /// let formatted = resolved.format_str(&Request { status: 200, bytes: 512 });
+    ///
+    /// assert_eq!(formatted.unwrap(), "200 512");
+    /// ```
+    pub fn resolve<C>(&self) -> Result<ResolvedFormat<'_, 'format, C>, format::Error<'format>>
+    where
+        C: IndexedContext,
+    {
+        let slots = self
+            .tokens()
+            .map(|token| match token {
+                Token::Literal(_) => Ok(None),
+                Token::Variable(name, _) => C::variable_index(name)
+                    .map(Some)
+                    .ok_or(format::Error::VariableNameError(name)),
+                Token::Include(name) => Err(format::Error::IncludeError(name)),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(ResolvedFormat {
+            format: self,
+            slots,
+            context: PhantomData,
+        })
+    }
+
+    /// Copies the format string, so the compiled format no longer borrows it.
+    pub fn into_owned(self) -> OwnedFormat {
+        OwnedFormat {
+            source: Box::from(self.source),
+            ast: self.ast.into_owned(),
+            literal_len: self.literal_len,
+            limits: self.limits,
+        }
     }
 
     #[cfg(test)]
     pub fn into_ast(self) -> Vec<Token<'format>> {
-        self.ast
+        self.tokens().collect()
+    }
+
+    pub(crate) fn from_parts(
+        source: &'format str,
+        ast: Cow<'format, [Node]>,
+        literal_len: usize,
+        limits: Limits,
+    ) -> CompiledFormat<'format> {
+        CompiledFormat {
+            source,
+            ast,
+            literal_len,
+            limits,
+        }
+    }
+
+    pub(crate) fn tokens(&self) -> impl Iterator<Item = Token<'format>> + '_ {
+        self.ast.iter().map(|node| node.token(self.source))
+    }
+}
+
+/// A compiled format that owns its format string. See [`CompiledFormat::into_owned`].
+///
+/// ```rust
+/// # #[cfg(feature = "std")] {
+/// use sformat_dynamic::{compile, context, OwnedFormat};
+///
+/// fn load() -> OwnedFormat {
+///     let source = String::from("{name} is {age}");
+///
+///     compile(&source).unwrap().into_owned()
+/// }
+///
+/// let format = load();
+/// let context = context! { name: "Ferris", age: 7 };
+/// let formatted = format.as_compiled().format_str(&context);
+///
+/// assert_eq!(formatted.unwrap(), "Ferris is 7");
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct OwnedFormat {
+    source: Box<str>,
+    ast: Vec<Node>,
+    literal_len: usize,
+    limits: Limits,
+}
+
+impl OwnedFormat {
+    /// Returns the format string.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Lends out the format as a `CompiledFormat`. This doesn't copy or recompile anything.
+    pub fn as_compiled(&self) -> CompiledFormat<'_> {
+        CompiledFormat {
+            source: &self.source,
+            ast: Cow::Borrowed(&self.ast),
+            literal_len: self.literal_len,
+            limits: self.limits,
+        }
+    }
+}
+
+/// A format with a bound context. See [`CompiledFormat::bind`].
+pub struct BoundFormat<'a, 'format, 'ctxt, C>
+where
+    C: ?Sized,
+{
+    format: &'a CompiledFormat<'format>,
+    context: &'ctxt C,
+}
+
+impl<'a, 'format, 'ctxt, C> fmt::Display for BoundFormat<'a, 'format, 'ctxt, C>
+where
+    C: Context<'ctxt> + ?Sized,
+    'format: 'ctxt,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.format
+            .format_fmt(f, self.context)
+            .map_err(|_err| fmt::Error)
+    }
+}
+
+/// A format with its variables resolved to indices of `C`. See [`CompiledFormat::resolve`].
+pub struct ResolvedFormat<'a, 'format, C> {
+    format: &'a CompiledFormat<'format>,
+    // The slot of each token, or `None` for literals.
+    slots: Vec<Option<usize>>,
+    context: PhantomData<fn(&C)>,
+}
+
+impl<'a, 'format, C> ResolvedFormat<'a, 'format, C>
+where
+    C: IndexedContext,
+{
+    fn get_variable<'ctxt>(
+        &self,
+        context: &'ctxt C,
+        i: usize,
+        name: Name<'ctxt>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'ctxt>> {
+        self.slots[i]
+            .and_then(|slot| context.get_indexed(slot))
+            .ok_or(format::Error::VariableNameError(name))
+    }
+
+    /// Writes the formatted string to an `io::Write`.
+    #[cfg(feature = "std")]
+    pub fn format<'ctxt, W>(
+        &self,
+        write: &mut W,
+        context: &'ctxt C,
+    ) -> Result<(), format::Error<'ctxt>>
+    where
+        W: io::Write,
+        'format: 'ctxt,
+    {
+        self.format
+            .format_io_with(write, None, |i, name| self.get_variable(context, i, name))
+    }
+
+    /// Writes the formatted string to a `fmt::Write`, like a `String` or a `fmt::Formatter`.
+    pub fn format_fmt<'ctxt, W>(
+        &self,
+        write: &mut W,
+        context: &'ctxt C,
+    ) -> Result<(), format::Error<'ctxt>>
+    where
+        W: fmt::Write + ?Sized,
+        'format: 'ctxt,
+    {
+        self.format
+            .format_fmt_with(write, None, |i, name| self.get_variable(context, i, name))
+    }
+
+    pub fn format_str<'ctxt>(&self, context: &'ctxt C) -> Result<String, format::Error<'ctxt>>
+    where
+        'format: 'ctxt,
+    {
+        let mut formatted = String::new();
+
+        self.format_fmt(&mut formatted, context)?;
+
+        Ok(formatted)
     }
 }
 
@@ -176,14 +823,28 @@ impl<'format> TryFrom<&'format str> for CompiledFormat<'format> {
 }
 
 #[cfg(test)]
+#[allow(clippy::excessive_precision)]
 mod tests {
-    use super::compile;
+    use super::{compile, compile_with_limits, CompileError, CompiledFormat};
     use crate::{
-        context::{DynPointer, TypedValue},
+        context::{DynPointer, OwnedValue, TypedValue},
+        escape,
         format::{self, Alignment, Fill, Flags, Format, SignFlag},
+        indexed::IndexedContext,
+        limits::Limits,
         token::Token,
     };
-    use std::collections::HashMap;
+    #[cfg(feature = "std")]
+    use std::io;
+    use std::{
+        boxed::Box,
+        collections::BTreeMap,
+        fmt,
+        num::{NonZeroI8, NonZeroU64},
+        string::{String, ToString},
+        time::Duration,
+        vec::Vec,
+    };
 
     macro_rules! compile_test {
         (
@@ -212,6 +873,16 @@ mod tests {
         ]
     }
 
+    compile_test! {
+        [test_compile_include]
+        COMPILE "{>header} {user.name}"
+        TO AST vec![
+            Token::Include("header"),
+            Token::Literal(" "),
+            Token::Variable("user.name", None)
+        ]
+    }
+
     compile_test! {
         [test_compile_empty]
         COMPILE ""
@@ -247,6 +918,26 @@ mod tests {
         ]
     }
 
+    compile_test! {
+        [test_compile_dotted_name]
+        COMPILE "{env.HOME}/{items.0:>4}"
+        TO AST vec![
+            Token::Variable("env.HOME", None),
+            Token::Literal("/"),
+            Token::Variable(
+                "items.0",
+                Some(
+                    Format::new(
+                        Some(Fill::new(None, Alignment::Right)),
+                        Flags::default(),
+                        Some(4u32),
+                        None
+                    )
+                )
+            )
+        ]
+    }
+
     compile_test! {
         [test_compile_fill]
         COMPILE "{number:*>5}"
@@ -371,14 +1062,14 @@ mod tests {
     format_test! {
         [test_format_literal]
         FORMAT "hello this is a test"
-        WITH CTXT HashMap::new();
+        WITH CTXT BTreeMap::<&str, TypedValue>::new();
         EQUALS "hello this is a test";
     }
 
     format_test! {
         [test_format_single_variable]
         FORMAT "hello this is a {test}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("test", TypedValue::Str("blergh"))
         ]);
         EQUALS "hello this is a blergh";
@@ -387,7 +1078,7 @@ mod tests {
     format_test! {
         [test_format_width_but_no_fill]
         FORMAT "Hello {name:10}!"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("name", TypedValue::Str("Ferris"))
         ]);
         EQUALS format!("Hello {:10}!", "Ferris");
@@ -396,7 +1087,7 @@ mod tests {
     format_test! {
         [test_format_multiple_variables]
         FORMAT "hello this is a {test}. my name is {name}. i am {age}."
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("test", TypedValue::Str("SERIOUS TEST")),
             ("name", TypedValue::Str("Ferris")),
             ("age", TypedValue::Uint(100))
@@ -407,7 +1098,7 @@ mod tests {
     format_test! {
         [test_format_multiple_floats]
         FORMAT "{float64} {float32} {infinity} {neg_infinity} {nan}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("float64", TypedValue::Float64(1938917398.13817f64)),
             ("float32", TypedValue::Float32(-15984.12351f32)),
             ("infinity", TypedValue::Float64(f64::INFINITY)),
@@ -427,7 +1118,7 @@ mod tests {
     format_test! {
         [test_format_extra_context]
         FORMAT "hello this is a {severity} test"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("severity", TypedValue::Str("SERIOUS")),
             ("level", TypedValue::Uint32(128u32))
         ]);
@@ -449,7 +1140,7 @@ mod tests {
     format_test! {
         [test_format_debug_struct]
         FORMAT "{struct}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("struct", TypedValue::Dyn(DynPointer::Debug(&TEST_STRUCT)))
         ]);
         EQUALS "TestStruct { name: \"test\", num: 1000 }";
@@ -458,7 +1149,7 @@ mod tests {
     format_test! {
         [test_format_fill_right_align]
         FORMAT "{number:*>10}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Uint32(0))
         ]);
         EQUALS format!("{:*>10}", 0);
@@ -467,7 +1158,7 @@ mod tests {
     format_test! {
         [test_format_fill_center_align_even]
         FORMAT "{number:0^9}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Int8(8))
         ]);
         EQUALS format!("{:0^9}", 8);
@@ -476,7 +1167,7 @@ mod tests {
     format_test! {
         [test_format_fill_center_align_uneven]
         FORMAT "{number:0^8}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Uint32(123))
         ]);
         EQUALS format!("{:0^8}", 123u32);
@@ -485,7 +1176,7 @@ mod tests {
     format_test! {
         [test_format_fill_center_align_too_long]
         FORMAT "{s:@^4}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("s", TypedValue::Str("hello world"))
         ]);
         EQUALS format!("{:@^4}", "hello world");
@@ -494,7 +1185,7 @@ mod tests {
     format_test! {
         [test_format_fill_default_char]
         FORMAT "{bool:<7}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("bool", TypedValue::Bool(false))
         ]);
         EQUALS format!("{:<#7}", false);
@@ -503,7 +1194,7 @@ mod tests {
     format_test! {
         [test_format_fill_numeric_left_align]
         FORMAT "{number:$<20}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Int32(-1000))
         ]);
         EQUALS format!("{:$<20}", -1000i32);
@@ -512,7 +1203,7 @@ mod tests {
     format_test! {
         [test_format_fill_numeric_right_align]
         FORMAT "{number:?>30}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Int32(-1000))
         ]);
         EQUALS format!("{:?>30}", -1000i32);
@@ -521,7 +1212,7 @@ mod tests {
     format_test! {
         [test_format_fill_left_align_signed_number]
         FORMAT "{number:X<+12}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Float32(-12.12f32))
         ]);
         EQUALS format!("{:X<+12}", -12.12f32);
@@ -530,7 +1221,7 @@ mod tests {
     format_test! {
         [test_format_fill_right_align_signed_number]
         FORMAT "{number:X>+18}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Float64(-1.125134f64))
         ]);
         EQUALS format!("{:X>+18}", -1.125134f64);
@@ -539,7 +1230,7 @@ mod tests {
     format_test! {
         [test_format_fill_left_align_unsigned_number]
         FORMAT "{number:.<+15}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Uint(319))
         ]);
         EQUALS format!("{:.<+15}", 319);
@@ -548,7 +1239,7 @@ mod tests {
     format_test! {
         [test_format_fill_right_align_unsigned_number]
         FORMAT "{number:.>+23}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Uint(32))
         ]);
         EQUALS format!("{:.>+23}", 32);
@@ -557,7 +1248,7 @@ mod tests {
     format_test! {
         [test_format_fill_center_align_unsigned_number]
         FORMAT "{number:.^+9}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Uint(9))
         ]);
         EQUALS format!("{:.^+9}", 9);
@@ -566,7 +1257,7 @@ mod tests {
     format_test! {
         [test_format_sign_positive_number]
         FORMAT "{number:+}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Int64(128i64))
         ]);
         EQUALS format!("{:+}", 128i64);
@@ -575,7 +1266,7 @@ mod tests {
     format_test! {
         [test_format_sign_zero]
         FORMAT "{number:+}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Uint8(0))
         ]);
         EQUALS format!("{:+}", 0);
@@ -584,7 +1275,7 @@ mod tests {
     format_test! {
         [test_format_sign_negative_number]
         FORMAT "{number:+}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Int32(i32::MIN))
         ]);
         EQUALS format!("{:+}", i32::MIN);
@@ -593,7 +1284,7 @@ mod tests {
     format_test! {
         [test_format_sign_float_nan]
         FORMAT "{number:+}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Float32(f32::NAN))
         ]);
         EQUALS format!("{:+}", f32::NAN);
@@ -602,7 +1293,7 @@ mod tests {
     format_test! {
         [test_format_sign_float_infinity]
         FORMAT "{number:+}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Float64(f64::NEG_INFINITY))
         ]);
         EQUALS format!("{:+}", f64::NEG_INFINITY);
@@ -611,7 +1302,7 @@ mod tests {
     format_test! {
         [test_format_fill_and_zero_flag_specified_signed_number]
         FORMAT "{number:.^+012}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Int(-194))
         ]);
         EQUALS format!("{:.^+012}", -194);
@@ -620,7 +1311,7 @@ mod tests {
     format_test! {
         [test_format_fill_and_zero_flag_specified_unsigned_number]
         FORMAT "{number:.<+09}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Uint(129))
         ]);
         EQUALS format!("{:.<+09}", 129);
@@ -629,7 +1320,7 @@ mod tests {
     format_test! {
         [test_format_fill_and_zero_flag_specified_non_numeric]
         FORMAT "{number:.<+021}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("number", TypedValue::Str("1000"))
         ]);
         EQUALS format!("{:.<+021}", "1000");
@@ -638,7 +1329,7 @@ mod tests {
     format_test! {
         [test_format_zero_float]
         FORMAT "{float:.15}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("float", TypedValue::Float64(0.0))
         ]);
         EQUALS format!("{:.15}", 0.0);
@@ -647,7 +1338,7 @@ mod tests {
     format_test! {
         [test_format_non_zero_float]
         FORMAT "{float:.15}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("float", TypedValue::Float64(10.1562))
         ]);
         EQUALS format!("{:.15}", 10.1562);
@@ -656,7 +1347,7 @@ mod tests {
     format_test! {
         [test_format_non_zero_usize]
         FORMAT "{usize:.15}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("usize", TypedValue::Uint(128))
         ]);
         EQUALS format!("{:.15}", 128);
@@ -665,7 +1356,7 @@ mod tests {
     format_test! {
         [test_format_fill_smaller_than_precision]
         FORMAT "{float:*<4.15}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("float", TypedValue::Float64(1.123456))
         ]);
         EQUALS format!("{:*<4.15}", 1.123456);
@@ -674,16 +1365,490 @@ mod tests {
     format_test! {
         [test_format_fill_larger_than_precision]
         FORMAT "{float:*<20.11}"
-        WITH CTXT HashMap::from([
+        WITH CTXT BTreeMap::from([
             ("float", TypedValue::Float64(283.1239))
         ]);
         EQUALS format!("{:*<20.11}", 283.1239);
     }
 
+    format_test! {
+        [test_format_owned_string_map]
+        FORMAT "{name:>8}|{role}"
+        WITH CTXT BTreeMap::from([
+            (String::from("name"), String::from("Ferris")),
+            (String::from("role"), String::from("crab"))
+        ]);
+        EQUALS format!("{:>8}|{}", "Ferris", "crab");
+    }
+
+    format_test! {
+        [test_format_btree_map]
+        FORMAT "{count:+05}"
+        WITH CTXT BTreeMap::from([
+            ("count", 42u32)
+        ]);
+        EQUALS format!("{:+05}", 42u32);
+    }
+
+    format_test! {
+        [test_format_slice_of_pairs]
+        FORMAT "{a}{b}{a}"
+        WITH CTXT [
+            ("a", TypedValue::Str("x")),
+            ("b", TypedValue::Bool(true)),
+            ("a", TypedValue::Str("shadowed"))
+        ].as_slice();
+        EQUALS "xtruex";
+    }
+
+    format_test! {
+        [test_format_owned_values]
+        FORMAT "{greeting}, {name}! {count:>4} {debug}"
+        WITH CTXT BTreeMap::from([
+            (String::from("greeting"), OwnedValue::from(format!("{}{}", "Hel", "lo"))),
+            (String::from("name"), OwnedValue::from("Ferris")),
+            (String::from("count"), OwnedValue::from(12u8)),
+            (String::from("debug"), OwnedValue::Debug(Box::new(Some(1))))
+        ]);
+        EQUALS "Hello, Ferris!   12 Some(1)";
+    }
+
+    format_test! {
+        [test_format_char]
+        FORMAT "{c}{c:*^5}{c:>+3}"
+        WITH CTXT BTreeMap::from([
+            ("c", TypedValue::Char('ß'))
+        ]);
+        EQUALS format!("{0}{0:*^5}{0:>+3}", 'ß');
+    }
+
+    format_test! {
+        [test_format_sign_fills_width]
+        FORMAT "{x:+1}|{x:+2}|{x:<+1}|{x:^+02}|{y:1}|{y:02}"
+        WITH CTXT BTreeMap::from([
+            ("x", TypedValue::Int32(5)),
+            ("y", TypedValue::Int32(-5))
+        ]);
+        EQUALS format!("{0:+1}|{0:+2}|{0:<+1}|{0:^+02}|{1:1}|{1:02}", 5, -5);
+    }
+
+    format_test! {
+        [test_format_zero_flag_negative]
+        FORMAT "{x:05}|{x:*>05}|{y:08.2}"
+        WITH CTXT BTreeMap::from([
+            ("x", TypedValue::Int32(-5)),
+            ("y", TypedValue::Float64(-1.5))
+        ]);
+        EQUALS format!("{:05}|{:*>05}|{:08.2}", -5, -5, -1.5);
+    }
+
+    format_test! {
+        [test_format_width_counts_chars]
+        FORMAT "{s:*^7}|{c:>3}|{c:ß<3}"
+        WITH CTXT BTreeMap::from([
+            ("s", TypedValue::Str("straße")),
+            ("c", TypedValue::Char('ß'))
+        ]);
+        EQUALS format!("{:*^7}|{1:>3}|{1:ß<3}", "straße", 'ß');
+    }
+
+    format_test! {
+        [test_format_precision_truncates]
+        FORMAT "{s:.3}|{s:>6.4}|{c:.0}|{b:.2}"
+        WITH CTXT BTreeMap::from([
+            ("s", TypedValue::Str("straße")),
+            ("c", TypedValue::Char('x')),
+            ("b", TypedValue::Bool(true))
+        ]);
+        EQUALS format!("{:.3}|{0:>6.4}|{:.0}|{:.2}", "straße", 'x', true);
+    }
+
+    format_test! {
+        [test_format_long_numbers]
+        FORMAT "{max:>320}|{tiny:.70}"
+        WITH CTXT BTreeMap::from([
+            ("max", TypedValue::Float64(f64::MAX)),
+            ("tiny", TypedValue::Float64(1e-30))
+        ]);
+        EQUALS format!("{:>320}|{:.70}", f64::MAX, 1e-30);
+    }
+
+    format_test! {
+        [test_format_numbers_align_right_by_default]
+        FORMAT "{n:5}|{f:8.2}|{s:5}|{inf:+}"
+        WITH CTXT BTreeMap::from([
+            ("n", TypedValue::Uint8(42)),
+            ("f", TypedValue::Float32(2.71)),
+            ("s", TypedValue::Str("ab")),
+            ("inf", TypedValue::Float64(f64::INFINITY))
+        ]);
+        EQUALS format!("{:5}|{:8.2}|{:5}|{:+}", 42u8, 2.71f32, "ab", f64::INFINITY);
+    }
+
+    format_test! {
+        [test_format_i128]
+        FORMAT "{min:+045}|{max:<42}"
+        WITH CTXT BTreeMap::from([
+            ("min", TypedValue::Int128(i128::MIN)),
+            ("max", TypedValue::Int128(i128::MAX))
+        ]);
+        EQUALS format!("{:+045}|{:<42}", i128::MIN, i128::MAX);
+    }
+
+    format_test! {
+        [test_format_u128]
+        FORMAT "{max:>+45}|{zero:+05}"
+        WITH CTXT BTreeMap::from([
+            ("max", TypedValue::Uint128(u128::MAX)),
+            ("zero", TypedValue::Uint128(0))
+        ]);
+        EQUALS format!("{:>+45}|{:+05}", u128::MAX, 0u128);
+    }
+
+    format_test! {
+        [test_format_non_zero]
+        FORMAT "{id:06}|{delta:+}"
+        WITH CTXT BTreeMap::from([
+            ("id", TypedValue::from(NonZeroU64::new(4096).unwrap())),
+            ("delta", TypedValue::from(NonZeroI8::new(-3).unwrap()))
+        ]);
+        EQUALS format!(
+            "{:06}|{:+}",
+            NonZeroU64::new(4096).unwrap(),
+            NonZeroI8::new(-3).unwrap()
+        );
+    }
+
+    format_test! {
+        [test_format_duration]
+        FORMAT "{elapsed}|{elapsed:.3}|{elapsed:>10.1}|{elapsed:+}|{zero:+}"
+        WITH CTXT BTreeMap::from([
+            ("elapsed", TypedValue::Duration(Duration::from_micros(1_532_718))),
+            ("zero", TypedValue::Duration(Duration::ZERO))
+        ]);
+        EQUALS format!(
+            "{0:?}|{0:.3?}|{0:>10.1?}|{0:+?}|{1:+?}",
+            Duration::from_micros(1_532_718),
+            Duration::ZERO
+        );
+    }
+
     format_test! {
         [test_format_missing_variable]
         FORMAT "hello this is a {severity} test"
-        WITH CTXT HashMap::new();
+        WITH CTXT BTreeMap::<&str, TypedValue>::new();
         FAILS WITH format::Error::VariableNameError("severity")
     }
+
+    /// Accepts a fixed number of bytes, then fails.
+    #[cfg(feature = "std")]
+    struct LimitedWriter(usize);
+
+    #[cfg(feature = "std")]
+    impl io::Write for LimitedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > self.0 {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "writer is full"));
+            }
+
+            self.0 -= buf.len();
+
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_format_io_errors() {
+        let format = compile("hello {name}").unwrap();
+        let context = BTreeMap::from([("name", TypedValue::Str("Ferris"))]);
+
+        assert!(matches!(
+            format.format(&mut LimitedWriter(3), &context),
+            Err(format::Error::WriteLiteralError(_))
+        ));
+        assert!(matches!(
+            format.format(&mut LimitedWriter(8), &context),
+            Err(format::Error::WriteVariableError("name", _))
+        ));
+    }
+
+    struct Greeting<'a> {
+        format: CompiledFormat<'a>,
+        name: &'a str,
+    }
+
+    impl fmt::Display for Greeting<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let context = [("name", TypedValue::Str(self.name))];
+
+            self.format
+                .format_fmt(f, &context[..])
+                .map_err(|_err| fmt::Error)
+        }
+    }
+
+    #[test]
+    fn test_format_fmt_in_display() {
+        let greeting = Greeting {
+            format: compile("Hello {name:*^10}!").unwrap(),
+            name: "Ferris",
+        };
+
+        assert_eq!(greeting.to_string(), format!("Hello {:*^10}!", "Ferris"));
+    }
+
+    #[test]
+    fn test_format_bind() {
+        let format = compile("{a}+{b}").unwrap();
+        let context = BTreeMap::from([("a", TypedValue::Uint(1)), ("b", TypedValue::Uint(2))]);
+
+        assert_eq!(format.bind(&context).to_string(), "1+2");
+    }
+
+    #[test]
+    fn test_format_escaped_skips_literals() {
+        let format = compile("<p title=\"{title}\">{body:&<8}</p>").unwrap();
+        let context = BTreeMap::from([("title", "\"quoted\""), ("body", "a<b")]);
+        let formatted = format.format_str_escaped(&context, &escape::Html);
+
+        assert_eq!(
+            formatted.unwrap(),
+            "<p title=\"&quot;quoted&quot;\">a&lt;b&&&&&</p>"
+        );
+
+        // Only the value is quoted, and the padding is measured before escaping.
+        let format = compile("{s:>6}|{n:+06}|{n:*^+6}").unwrap();
+        let context = BTreeMap::from([("s", TypedValue::Str("it's")), ("n", TypedValue::Int(-5))]);
+
+        assert_eq!(
+            format.format_str_escaped(&context, &escape::Shell).unwrap(),
+            r"  'it'\''s'|-0000'5'|**'-5'**"
+        );
+
+        #[cfg(feature = "std")]
+        {
+            let format = compile("<p title=\"{title}\">{body:&<8}</p>").unwrap();
+            let context = BTreeMap::from([("title", "\"quoted\""), ("body", "a<b")]);
+            let mut buf = Vec::new();
+
+            format
+                .format_escaped(&mut buf, &context, &escape::Html)
+                .unwrap();
+
+            assert_eq!(
+                buf,
+                format
+                    .format_str_escaped(&context, &escape::Html)
+                    .unwrap()
+                    .as_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn test_compile_limits() {
+        assert!(matches!(
+            compile("{x:4294967295}"),
+            Err(CompileError::WidthLimitError(name, 65535)) if name == "x"
+        ));
+        assert_eq!(
+            compile("{x:4294967295}")
+                .unwrap_err()
+                .offset("{x:4294967295}"),
+            None
+        );
+        assert!(compile_with_limits("{x:4294967295}", Limits::UNLIMITED).is_ok());
+
+        let limits = Limits::new()
+            .with_max_width(8)
+            .with_max_precision(2)
+            .with_max_tokens(3);
+
+        assert!(compile_with_limits("{a:8.2} {b:<8}", limits).is_ok());
+        assert!(matches!(
+            compile_with_limits("{a:9}", limits),
+            Err(CompileError::WidthLimitError(_, 8))
+        ));
+        assert!(matches!(
+            compile_with_limits("{a:.3}", limits),
+            Err(CompileError::PrecisionLimitError(name, 2)) if name == "a"
+        ));
+        assert!(matches!(
+            compile_with_limits("{a} {b} {c}", limits),
+            Err(CompileError::TokenLimitError(3))
+        ));
+    }
+
+    #[test]
+    fn test_format_output_limit() {
+        let limits = Limits::new().with_max_output_bytes(12);
+        let format = compile_with_limits("Hello {name:*>5}!", limits).unwrap();
+        let context = BTreeMap::from([("name", "Ferris")]);
+
+        assert!(matches!(
+            format.format_str(&context),
+            Err(format::Error::OutputLimitError(12))
+        ));
+
+        let context = BTreeMap::from([("name", "Fe")]);
+
+        assert_eq!(format.format_str(&context).unwrap(), "Hello ***Fe!");
+
+        #[cfg(feature = "std")]
+        {
+            let context = BTreeMap::from([("name", "Ferris")]);
+            let mut buf = Vec::new();
+
+            assert!(matches!(
+                format.format(&mut buf, &context),
+                Err(format::Error::OutputLimitError(12))
+            ));
+            assert_eq!(buf, b"Hello Ferris");
+        }
+
+        // Escaped padding is written straight to the limited writer, rather than being
+        // buffered first.
+        let limits = Limits::UNLIMITED.with_max_output_bytes(16);
+        let format = compile_with_limits("<{x:4294967295}>", limits).unwrap();
+        let context = BTreeMap::from([("x", "a<b")]);
+
+        assert!(matches!(
+            format.format_str_escaped(&context, &escape::Html),
+            Err(format::Error::OutputLimitError(16))
+        ));
+
+        // Widths under the default cap still add up, so the output is capped by default.
+        let format_str = "{x:65535}".repeat(17);
+        let format = compile(&format_str).unwrap();
+
+        assert!(matches!(
+            format.format_str(&context),
+            Err(format::Error::OutputLimitError(1048576))
+        ));
+    }
+
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    impl IndexedContext for Point {
+        const LEN: usize = 2;
+
+        fn variable_index(name: &str) -> Option<usize> {
+            match name {
+                "x" => Some(0),
+                "y" => Some(1),
+                _ => None,
+            }
+        }
+
+        fn get_indexed(&self, index: usize) -> Option<TypedValue<'_>> {
+            match index {
+                0 => Some(TypedValue::Int32(self.x)),
+                1 => Some(TypedValue::Int32(self.y)),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_format_resolved() {
+        let format = compile("({x:+}, {y:>4}) {x}").unwrap();
+        let resolved = format.resolve::<Point>().unwrap();
+
+        for (x, y) in [(1, -2), (-30, 40)] {
+            assert_eq!(
+                resolved.format_str(&Point { x, y }).unwrap(),
+                format!("({:+}, {:>4}) {}", x, y, x)
+            );
+        }
+
+        let format = compile("{x}{z}").unwrap();
+
+        assert!(matches!(
+            format.resolve::<Point>(),
+            Err(format::Error::VariableNameError("z"))
+        ));
+    }
+
+    #[test]
+    fn test_estimate_len() {
+        let format = compile("Hello {name:ß^9}, you are {age:+04}! {ratio:.3}").unwrap();
+        let context = BTreeMap::from([
+            ("name", TypedValue::Str("Ferris")),
+            ("age", TypedValue::Int8(7)),
+            ("ratio", TypedValue::Float64(0.5)),
+        ]);
+        let formatted = format.format_str(&context).unwrap();
+
+        assert_eq!(format.literal_len(), "Hello , you are ! ".len());
+        assert_eq!(format.estimate_len(&context), formatted.len());
+
+        // The padding is counted, not written, so a huge width is cheap to measure.
+        let format = compile_with_limits("{name:4294967295}", Limits::UNLIMITED).unwrap();
+
+        assert_eq!(format.estimate_len(&context), 4294967295);
+    }
+
+    #[test]
+    fn test_variables() {
+        let format = compile("{a} {{ {b:>4} {>header} {a}").unwrap();
+
+        assert_eq!(format.variables().collect::<Vec<_>>(), ["a", "b", "a"]);
+    }
+
+    #[test]
+    fn test_format_into_reuses_buffer() {
+        let format = compile("{name:>8}|{count}").unwrap();
+        let mut buf = String::new();
+
+        format
+            .format_into(
+                &mut buf,
+                &BTreeMap::from([
+                    ("name", TypedValue::Str("Ferris")),
+                    ("count", TypedValue::Uint(99)),
+                ]),
+            )
+            .unwrap();
+
+        assert_eq!(buf, "  Ferris|99");
+
+        let ptr = buf.as_ptr();
+
+        buf.clear();
+        format
+            .format_into(
+                &mut buf,
+                &BTreeMap::from([
+                    ("name", TypedValue::Str("Crab")),
+                    ("count", TypedValue::Uint(1)),
+                ]),
+            )
+            .unwrap();
+
+        assert_eq!(buf, "    Crab|1");
+        assert_eq!(buf.as_ptr(), ptr);
+
+        // The output is reserved for up front, so a fresh buffer is allocated once.
+        let mut buf = String::new();
+        format
+            .format_into(
+                &mut buf,
+                &BTreeMap::from([
+                    ("name", TypedValue::Str("Ferris")),
+                    ("count", TypedValue::Uint(12345)),
+                ]),
+            )
+            .unwrap();
+
+        assert_eq!(buf, "  Ferris|12345");
+        assert_eq!(buf.capacity(), buf.len());
+    }
 }
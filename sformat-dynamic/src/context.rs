@@ -1,12 +1,29 @@
 use crate::{format, Name};
-use std::{
-    collections::HashMap,
-    fmt::{Debug, Display},
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
 };
+#[cfg(any(feature = "std", feature = "indexmap"))]
+use core::hash::{BuildHasher, Hash};
+use core::{
+    borrow::Borrow,
+    fmt::{self, Debug, Display},
+    num::{
+        NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
+        NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+    },
+    str,
+    time::Duration,
+};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
 
 /// Wraps the string representation of a value.
 pub(crate) enum StringRepresentation<'a> {
     Borrowed(&'a str),
+    Inline(InlineBuf),
     Owned(String),
 }
 
@@ -14,11 +31,89 @@ impl<'a> AsRef<str> for StringRepresentation<'a> {
     fn as_ref(&self) -> &str {
         match self {
             StringRepresentation::Borrowed(str_ref) => str_ref,
+            StringRepresentation::Inline(buf) => buf.as_str(),
             StringRepresentation::Owned(str_owned) => &str_owned[..],
         }
     }
 }
 
+/// A fixed size buffer, large enough for any integer and most floats and durations.
+pub(crate) struct InlineBuf {
+    buf: [u8; InlineBuf::CAPACITY],
+    len: usize,
+}
+
+impl InlineBuf {
+    const CAPACITY: usize = 64;
+
+    fn new() -> InlineBuf {
+        InlineBuf {
+            buf: [0; InlineBuf::CAPACITY],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // Only whole `str`s are ever copied into the buffer, so it's always valid UTF-8.
+        str::from_utf8(&self.buf[..self.len]).unwrap_or_default()
+    }
+}
+
+impl fmt::Write for InlineBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+
+        Ok(())
+    }
+}
+
+/// Appends to the representation, moving it to a `String` once it no longer fits inline.
+impl fmt::Write for StringRepresentation<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self {
+            StringRepresentation::Owned(owned) => owned.push_str(s),
+            StringRepresentation::Inline(buf) if buf.len + s.len() <= InlineBuf::CAPACITY => {
+                buf.write_str(s)?
+            }
+            _ => {
+                let current = self.as_ref();
+                let mut owned = String::with_capacity(current.len() + s.len());
+
+                owned.push_str(current);
+                owned.push_str(s);
+                *self = StringRepresentation::Owned(owned);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats `args` into an `InlineBuf`, and only allocates if it doesn't fit.
+fn format_repr(args: fmt::Arguments<'_>) -> StringRepresentation<'static> {
+    let mut repr = StringRepresentation::Inline(InlineBuf::new());
+
+    // Writing to the representation never fails, so an error can only come from a
+    // `Display` implementation, and the output so far is kept like `ToString` does.
+    let _ = fmt::Write::write_fmt(&mut repr, args);
+
+    repr
+}
+
+/// Truncates `value` to at most `precision` characters.
+fn truncate(value: &str, precision: Option<u32>) -> &str {
+    match precision.and_then(|precision| value.char_indices().nth(precision as usize)) {
+        Some((end, _)) => &value[..end],
+        None => value,
+    }
+}
+
 /// Sign of a numeric value.
 #[derive(Clone, Copy)]
 pub(crate) enum Sign {
@@ -27,12 +122,12 @@ pub(crate) enum Sign {
     Zero,
 }
 
-impl Into<u8> for Sign {
-    fn into(self) -> u8 {
-        match self {
-            Sign::Positive => b'+',
-            Sign::Negative => b'-',
-            Sign::Zero => b'+',
+impl From<Sign> for char {
+    fn from(sign: Sign) -> char {
+        match sign {
+            Sign::Positive => '+',
+            Sign::Negative => '-',
+            Sign::Zero => '+',
         }
     }
 }
@@ -48,12 +143,15 @@ pub enum DynPointer<'a> {
 #[derive(Clone, Copy)]
 pub enum TypedValue<'a> {
     Str(&'a str),
+    Char(char),
     Int(isize),
+    Int128(i128),
     Int64(i64),
     Int32(i32),
     Int16(i16),
     Int8(i8),
     Uint(usize),
+    Uint128(u128),
     Uint64(u64),
     Uint32(u32),
     Uint16(u16),
@@ -61,6 +159,7 @@ pub enum TypedValue<'a> {
     Float32(f32),
     Float64(f64),
     Bool(bool),
+    Duration(Duration),
     Dyn(DynPointer<'a>),
 }
 
@@ -69,29 +168,42 @@ impl<'a> TypedValue<'a> {
         macro_rules! handle_numeric_pattern {
             ($e:expr) => (
                 if let Some(precision) = precision {
-                    StringRepresentation::Owned(format!("{1:.0$}", precision as usize, $e))
+                    format_repr(format_args!("{1:.0$}", precision as usize, $e))
                 } else {
-                    StringRepresentation::Owned($e.to_string())
+                    format_repr(format_args!("{}", $e))
                 }
             )
         }
 
         match self {
-            TypedValue::Str(inner) => StringRepresentation::Borrowed(inner),
+            // Like `std::fmt`, precision truncates strings, chars and booleans.
+            TypedValue::Str(inner) => StringRepresentation::Borrowed(truncate(inner, precision)),
+            TypedValue::Char(_) if precision == Some(0) => StringRepresentation::Borrowed(""),
+            TypedValue::Char(c) => format_repr(format_args!("{}", c)),
             TypedValue::Int(num) => handle_numeric_pattern!(num),
+            TypedValue::Int128(num) => handle_numeric_pattern!(num),
             TypedValue::Int64(num) => handle_numeric_pattern!(num),
             TypedValue::Int32(num) => handle_numeric_pattern!(num),
             TypedValue::Int16(num) => handle_numeric_pattern!(num),
             TypedValue::Int8(num) => handle_numeric_pattern!(num),
             TypedValue::Uint(num) => handle_numeric_pattern!(num),
+            TypedValue::Uint128(num) => handle_numeric_pattern!(num),
             TypedValue::Uint64(num) => handle_numeric_pattern!(num),
             TypedValue::Uint32(num) => handle_numeric_pattern!(num),
             TypedValue::Uint16(num) => handle_numeric_pattern!(num),
             TypedValue::Uint8(num) => handle_numeric_pattern!(num),
             TypedValue::Float32(num) => handle_numeric_pattern!(num),
             TypedValue::Float64(num) => handle_numeric_pattern!(num),
-            TypedValue::Bool(true) => StringRepresentation::Borrowed("true"),
-            TypedValue::Bool(false) => StringRepresentation::Borrowed("false"),
+            TypedValue::Bool(true) => StringRepresentation::Borrowed(truncate("true", precision)),
+            TypedValue::Bool(false) => StringRepresentation::Borrowed(truncate("false", precision)),
+            // Durations only implement `Debug`, which also supports precision.
+            TypedValue::Duration(duration) => {
+                if let Some(precision) = precision {
+                    format_repr(format_args!("{1:.0$?}", precision as usize, duration))
+                } else {
+                    format_repr(format_args!("{:?}", duration))
+                }
+            }
             TypedValue::Dyn(DynPointer::Debug(debug)) => {
                 StringRepresentation::Owned(format!("{:?}", debug))
             }
@@ -105,11 +217,13 @@ impl<'a> TypedValue<'a> {
         matches!(
             self,
             TypedValue::Int(_)
+                | TypedValue::Int128(_)
                 | TypedValue::Int64(_)
                 | TypedValue::Int32(_)
                 | TypedValue::Int16(_)
                 | TypedValue::Int8(_)
                 | TypedValue::Uint(_)
+                | TypedValue::Uint128(_)
                 | TypedValue::Uint64(_)
                 | TypedValue::Uint32(_)
                 | TypedValue::Uint16(_)
@@ -142,46 +256,377 @@ impl<'a> TypedValue<'a> {
 
         macro_rules! match_float {
             ($e:expr) => {
-                if $e.is_finite() {
-                    if $e.is_sign_positive() {
-                        Some(Sign::Positive)
-                    } else if $e.is_sign_negative() {
-                        Some(Sign::Negative)
-                    } else {
-                        unreachable!()
-                    }
+                if $e.is_nan() {
+                    None
+                } else if $e.is_sign_positive() {
+                    Some(Sign::Positive)
                 } else {
-                    None // NaN, INFINITY
+                    Some(Sign::Negative)
                 }
             };
         }
 
         match self {
             TypedValue::Int(num) => match_signed_int!(num),
+            TypedValue::Int128(num) => match_signed_int!(num),
             TypedValue::Int64(num) => match_signed_int!(num),
             TypedValue::Int32(num) => match_signed_int!(num),
             TypedValue::Int16(num) => match_signed_int!(num),
             TypedValue::Int8(num) => match_signed_int!(num),
             TypedValue::Uint(num) => match_unsigned_int!(num),
+            TypedValue::Uint128(num) => match_unsigned_int!(num),
             TypedValue::Uint64(num) => match_unsigned_int!(num),
             TypedValue::Uint32(num) => match_unsigned_int!(num),
             TypedValue::Uint16(num) => match_unsigned_int!(num),
             TypedValue::Uint8(num) => match_unsigned_int!(num),
             TypedValue::Float32(num) => match_float!(num),
             TypedValue::Float64(num) => match_float!(num),
+            // Durations are never negative, but `Debug` still writes a "+" sign.
+            TypedValue::Duration(duration) if duration.is_zero() => Some(Sign::Zero),
+            TypedValue::Duration(_) => Some(Sign::Positive),
             _ => None,
         }
     }
 }
 
+macro_rules! impl_from_for_typed_value {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl<'a> From<$ty> for TypedValue<'a> {
+                fn from(val: $ty) -> TypedValue<'a> {
+                    TypedValue::$variant(val)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_for_typed_value! {
+    &'a str => Str,
+    char => Char,
+    isize => Int,
+    i128 => Int128,
+    i64 => Int64,
+    i32 => Int32,
+    i16 => Int16,
+    i8 => Int8,
+    usize => Uint,
+    u128 => Uint128,
+    u64 => Uint64,
+    u32 => Uint32,
+    u16 => Uint16,
+    u8 => Uint8,
+    f32 => Float32,
+    f64 => Float64,
+    bool => Bool,
+    Duration => Duration,
+    DynPointer<'a> => Dyn,
+}
+
+impl<'a> From<&'a String> for TypedValue<'a> {
+    fn from(val: &'a String) -> TypedValue<'a> {
+        TypedValue::Str(val)
+    }
+}
+
+impl<'a> From<&'a dyn Debug> for TypedValue<'a> {
+    fn from(val: &'a dyn Debug) -> TypedValue<'a> {
+        TypedValue::Dyn(DynPointer::Debug(val))
+    }
+}
+
+impl<'a> From<&'a dyn Display> for TypedValue<'a> {
+    fn from(val: &'a dyn Display) -> TypedValue<'a> {
+        TypedValue::Dyn(DynPointer::Display(val))
+    }
+}
+
+/// Resolves variables by name.
+///
+/// `get_variable` takes `&'ctxt self`, so values can be borrowed from the context itself,
+/// e.g. from a `HashMap<String, String>`. The context's lifetime therefore appears in its
+/// type, and an empty map may need its value type spelled out, e.g.
+/// `HashMap::<&str, TypedValue>::new()`.
 pub trait Context<'ctxt> {
-    fn get_variable<'b>(&self, name: Name<'b>) -> Result<TypedValue<'ctxt>, format::Error<'b>>;
+    fn get_variable<'b>(
+        &'ctxt self,
+        name: Name<'b>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'b>>;
+}
+
+/// A value stored in a map or slice context.
+///
+/// Values are borrowed from the context while formatting, so owned strings can be
+/// used without copying them into a `TypedValue` first.
+pub trait ContextValue<'ctxt> {
+    fn typed_value(&'ctxt self) -> TypedValue<'ctxt>;
+}
+
+impl<'ctxt, 'a: 'ctxt> ContextValue<'ctxt> for TypedValue<'a> {
+    fn typed_value(&'ctxt self) -> TypedValue<'ctxt> {
+        *self
+    }
+}
+
+impl<'ctxt> ContextValue<'ctxt> for String {
+    fn typed_value(&'ctxt self) -> TypedValue<'ctxt> {
+        TypedValue::Str(self)
+    }
+}
+
+impl<'ctxt> ContextValue<'ctxt> for str {
+    fn typed_value(&'ctxt self) -> TypedValue<'ctxt> {
+        TypedValue::Str(self)
+    }
+}
+
+impl<'ctxt, 'a: 'ctxt> ContextValue<'ctxt> for &'a str {
+    fn typed_value(&'ctxt self) -> TypedValue<'ctxt> {
+        TypedValue::Str(self)
+    }
 }
 
-impl<'ctxt> Context<'ctxt> for HashMap<Name<'_>, TypedValue<'ctxt>> {
-    fn get_variable<'b>(&self, name: Name<'b>) -> Result<TypedValue<'ctxt>, format::Error<'b>> {
+macro_rules! impl_context_value {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl<'ctxt> ContextValue<'ctxt> for $ty {
+                fn typed_value(&'ctxt self) -> TypedValue<'ctxt> {
+                    TypedValue::$variant(*self)
+                }
+            }
+        )*
+    };
+}
+
+impl_context_value! {
+    char => Char,
+    isize => Int,
+    i128 => Int128,
+    i64 => Int64,
+    i32 => Int32,
+    i16 => Int16,
+    i8 => Int8,
+    usize => Uint,
+    u128 => Uint128,
+    u64 => Uint64,
+    u32 => Uint32,
+    u16 => Uint16,
+    u8 => Uint8,
+    f32 => Float32,
+    f64 => Float64,
+    bool => Bool,
+    Duration => Duration,
+}
+
+/// Owned counterpart of `TypedValue`.
+///
+/// Useful for building contexts from computed values, which would otherwise have to
+/// outlive the context.
+pub enum OwnedValue {
+    Str(String),
+    Char(char),
+    Int(isize),
+    Int128(i128),
+    Int64(i64),
+    Int32(i32),
+    Int16(i16),
+    Int8(i8),
+    Uint(usize),
+    Uint128(u128),
+    Uint64(u64),
+    Uint32(u32),
+    Uint16(u16),
+    Uint8(u8),
+    Float32(f32),
+    Float64(f64),
+    Bool(bool),
+    Duration(Duration),
+    Debug(Box<dyn Debug>),
+    Display(Box<dyn Display>),
+}
+
+impl<'ctxt> ContextValue<'ctxt> for OwnedValue {
+    fn typed_value(&'ctxt self) -> TypedValue<'ctxt> {
+        match self {
+            OwnedValue::Str(string) => TypedValue::Str(string),
+            OwnedValue::Char(c) => TypedValue::Char(*c),
+            OwnedValue::Int(num) => TypedValue::Int(*num),
+            OwnedValue::Int128(num) => TypedValue::Int128(*num),
+            OwnedValue::Int64(num) => TypedValue::Int64(*num),
+            OwnedValue::Int32(num) => TypedValue::Int32(*num),
+            OwnedValue::Int16(num) => TypedValue::Int16(*num),
+            OwnedValue::Int8(num) => TypedValue::Int8(*num),
+            OwnedValue::Uint(num) => TypedValue::Uint(*num),
+            OwnedValue::Uint128(num) => TypedValue::Uint128(*num),
+            OwnedValue::Uint64(num) => TypedValue::Uint64(*num),
+            OwnedValue::Uint32(num) => TypedValue::Uint32(*num),
+            OwnedValue::Uint16(num) => TypedValue::Uint16(*num),
+            OwnedValue::Uint8(num) => TypedValue::Uint8(*num),
+            OwnedValue::Float32(num) => TypedValue::Float32(*num),
+            OwnedValue::Float64(num) => TypedValue::Float64(*num),
+            OwnedValue::Bool(boolean) => TypedValue::Bool(*boolean),
+            OwnedValue::Duration(duration) => TypedValue::Duration(*duration),
+            OwnedValue::Debug(debug) => TypedValue::Dyn(DynPointer::Debug(debug.as_ref())),
+            OwnedValue::Display(display) => TypedValue::Dyn(DynPointer::Display(display.as_ref())),
+        }
+    }
+}
+
+macro_rules! impl_from_for_owned_value {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$ty> for OwnedValue {
+                fn from(val: $ty) -> OwnedValue {
+                    OwnedValue::$variant(val)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_for_owned_value! {
+    String => Str,
+    char => Char,
+    isize => Int,
+    i128 => Int128,
+    i64 => Int64,
+    i32 => Int32,
+    i16 => Int16,
+    i8 => Int8,
+    usize => Uint,
+    u128 => Uint128,
+    u64 => Uint64,
+    u32 => Uint32,
+    u16 => Uint16,
+    u8 => Uint8,
+    f32 => Float32,
+    f64 => Float64,
+    bool => Bool,
+    Duration => Duration,
+    Box<dyn Display> => Display,
+}
+
+impl From<&str> for OwnedValue {
+    fn from(val: &str) -> OwnedValue {
+        OwnedValue::Str(val.to_string())
+    }
+}
+
+/// `NonZero*` integers are represented by the variant of their underlying integer type.
+macro_rules! impl_non_zero {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl<'ctxt> ContextValue<'ctxt> for $ty {
+                fn typed_value(&'ctxt self) -> TypedValue<'ctxt> {
+                    TypedValue::$variant(self.get())
+                }
+            }
+
+            impl<'a> From<$ty> for TypedValue<'a> {
+                fn from(val: $ty) -> TypedValue<'a> {
+                    TypedValue::$variant(val.get())
+                }
+            }
+
+            impl From<$ty> for OwnedValue {
+                fn from(val: $ty) -> OwnedValue {
+                    OwnedValue::$variant(val.get())
+                }
+            }
+        )*
+    };
+}
+
+impl_non_zero! {
+    NonZeroIsize => Int,
+    NonZeroI128 => Int128,
+    NonZeroI64 => Int64,
+    NonZeroI32 => Int32,
+    NonZeroI16 => Int16,
+    NonZeroI8 => Int8,
+    NonZeroUsize => Uint,
+    NonZeroU128 => Uint128,
+    NonZeroU64 => Uint64,
+    NonZeroU32 => Uint32,
+    NonZeroU16 => Uint16,
+    NonZeroU8 => Uint8,
+}
+
+impl<'ctxt, C> Context<'ctxt> for &C
+where
+    C: Context<'ctxt> + ?Sized,
+{
+    fn get_variable<'b>(
+        &'ctxt self,
+        name: Name<'b>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'b>> {
+        (**self).get_variable(name)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'ctxt, K, V, S> Context<'ctxt> for HashMap<K, V, S>
+where
+    K: Borrow<str> + Eq + Hash,
+    V: ContextValue<'ctxt>,
+    S: BuildHasher,
+{
+    fn get_variable<'b>(
+        &'ctxt self,
+        name: Name<'b>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'b>> {
+        self.get(name)
+            .map(ContextValue::typed_value)
+            .ok_or(format::Error::VariableNameError(name))
+    }
+}
+
+impl<'ctxt, K, V> Context<'ctxt> for BTreeMap<K, V>
+where
+    K: Borrow<str> + Ord,
+    V: ContextValue<'ctxt>,
+{
+    fn get_variable<'b>(
+        &'ctxt self,
+        name: Name<'b>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'b>> {
+        self.get(name)
+            .map(ContextValue::typed_value)
+            .ok_or(format::Error::VariableNameError(name))
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<'ctxt, K, V, S> Context<'ctxt> for indexmap::IndexMap<K, V, S>
+where
+    K: Borrow<str> + Eq + Hash,
+    V: ContextValue<'ctxt>,
+    S: BuildHasher,
+{
+    fn get_variable<'b>(
+        &'ctxt self,
+        name: Name<'b>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'b>> {
         self.get(name)
-            .copied()
+            .map(ContextValue::typed_value)
+            .ok_or(format::Error::VariableNameError(name))
+    }
+}
+
+/// Slices of pairs are searched linearly. If a name appears more than once, the first
+/// pair wins.
+impl<'ctxt, K, V> Context<'ctxt> for [(K, V)]
+where
+    K: Borrow<str>,
+    V: ContextValue<'ctxt>,
+{
+    fn get_variable<'b>(
+        &'ctxt self,
+        name: Name<'b>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'b>> {
+        self.iter()
+            .find(|(key, _)| key.borrow() == name)
+            .map(|(_, value)| value.typed_value())
             .ok_or(format::Error::VariableNameError(name))
     }
 }
@@ -0,0 +1,110 @@
+use crate::{
+    context::{Context, TypedValue},
+    format, Name,
+};
+use std::{collections::HashMap, env};
+
+/// Resolves variables from the process environment.
+///
+/// The environment is captured when the context is created, so later changes to the
+/// environment aren't visible. Variables that aren't valid unicode are skipped. Values
+/// are resolved as `TypedValue::Str`.
+#[derive(Debug, Clone)]
+pub struct EnvContext {
+    vars: HashMap<String, String>,
+}
+
+impl EnvContext {
+    /// Captures every environment variable.
+    pub fn new() -> EnvContext {
+        let vars = env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+            .collect();
+
+        EnvContext { vars }
+    }
+
+    /// Only keeps variables whose name starts with `prefix`.
+    ///
+    /// Variables are still referenced by their full name, e.g. `{APP_PORT}`.
+    pub fn with_prefix(mut self, prefix: &str) -> EnvContext {
+        self.vars.retain(|key, _| key.starts_with(prefix));
+        self
+    }
+
+    /// Only keeps the listed variables.
+    ///
+    /// This can be combined with [`EnvContext::with_prefix`], in which case a variable
+    /// has to pass both filters.
+    pub fn with_allow_list<I, S>(mut self, names: I) -> EnvContext
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let allowed: Vec<S> = names.into_iter().collect();
+
+        self.vars
+            .retain(|key, _| allowed.iter().any(|name| name.as_ref() == key));
+        self
+    }
+}
+
+impl Default for EnvContext {
+    fn default() -> EnvContext {
+        EnvContext::new()
+    }
+}
+
+impl<'ctxt> Context<'ctxt> for EnvContext {
+    fn get_variable<'b>(
+        &'ctxt self,
+        name: Name<'b>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'b>> {
+        self.vars
+            .get(name)
+            .map(|value| TypedValue::Str(value))
+            .ok_or(format::Error::VariableNameError(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EnvContext;
+    use crate::{compile, format};
+    use std::env;
+
+    #[test]
+    fn test_env_context() {
+        env::set_var("SFORMAT_DYNAMIC_TEST_ENV_USER", "ferris");
+
+        let context = EnvContext::new();
+        let formatted = compile("{SFORMAT_DYNAMIC_TEST_ENV_USER:>8}")
+            .unwrap()
+            .format_str(&context);
+
+        assert_eq!(formatted.unwrap(), "  ferris");
+    }
+
+    #[test]
+    fn test_env_context_filters() {
+        env::set_var("SFORMAT_DYNAMIC_TEST_FILTER_A", "a");
+        env::set_var("SFORMAT_DYNAMIC_TEST_FILTER_B", "b");
+
+        let context = EnvContext::new()
+            .with_prefix("SFORMAT_DYNAMIC_TEST_FILTER_")
+            .with_allow_list(["SFORMAT_DYNAMIC_TEST_FILTER_A", "PATH"]);
+        let format = compile("{SFORMAT_DYNAMIC_TEST_FILTER_A}").unwrap();
+
+        assert_eq!(format.format_str(&context).unwrap(), "a");
+
+        for name in ["SFORMAT_DYNAMIC_TEST_FILTER_B", "PATH"] {
+            let source = format!("{{{}}}", name);
+            let formatted = compile(&source).unwrap().format_str(&context);
+
+            assert!(matches!(
+                formatted,
+                Err(format::Error::VariableNameError(missing)) if missing == name
+            ));
+        }
+    }
+}
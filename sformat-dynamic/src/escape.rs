@@ -0,0 +1,252 @@
+//! Escaping of variable output.
+//!
+//! An [`Escaper`] is applied to the value of each variable, but never to the literals of
+//! the format string. Fill and padding come from the format string too, so they're written
+//! as is around the escaped value, and the width is measured before escaping. Pick the
+//! escaper that matches where the output ends up, e.g. [`Html`] for HTML text and
+//! attribute values.
+//!
+//! ```rust
+//! # #[cfg(feature = "std")] {
+//! use sformat_dynamic::{compile, context, escape};
+//!
+//! let format = compile("<b>{name}</b>").unwrap();
+//! let context = context! { name: "<script>" };
+//! let formatted = format.format_str_escaped(&context, &escape::Html).unwrap();
+//!
+//! assert_eq!(formatted, "<b>&lt;script&gt;</b>");
+//! # }
+//! ```
+
+use core::fmt::{self, Write};
+
+/// Escapes the output of a variable.
+pub trait Escaper {
+    /// Writes the escaped form of `value`.
+    fn escape(&self, value: &str, write: &mut dyn Write) -> fmt::Result;
+}
+
+/// Forwards to a possibly unsized writer, so it can be passed on as a `&mut dyn Write`.
+struct Forward<'w, W: ?Sized>(&'w mut W);
+
+impl<W> Write for Forward<'_, W>
+where
+    W: Write + ?Sized,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_str(s)
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.0.write_char(c)
+    }
+}
+
+/// Writes `value` escaped with `escaper`, or as is if there isn't one.
+pub(crate) fn write_escaped<W>(
+    value: &str,
+    escaper: Option<&dyn Escaper>,
+    write: &mut W,
+) -> fmt::Result
+where
+    W: Write + ?Sized,
+{
+    match escaper {
+        Some(escaper) => escaper.escape(value, &mut Forward(write)),
+        None => write.write_str(value),
+    }
+}
+
+/// Writes `value`, writing the characters `needs_escape` matches with `escape` instead.
+///
+/// The characters in between are written in runs, rather than one at a time.
+fn escape_chars<P, E>(value: &str, write: &mut dyn Write, needs_escape: P, escape: E) -> fmt::Result
+where
+    P: Fn(char) -> bool,
+    E: Fn(char, &mut dyn Write) -> fmt::Result,
+{
+    let mut start = 0;
+
+    for (i, c) in value.char_indices().filter(|&(_, c)| needs_escape(c)) {
+        if start < i {
+            write.write_str(&value[start..i])?;
+        }
+
+        escape(c, write)?;
+        start = i + c.len_utf8();
+    }
+
+    if start < value.len() {
+        write.write_str(&value[start..])?;
+    }
+
+    Ok(())
+}
+
+/// Escapes text for HTML element content and quoted attribute values.
+///
+/// Replaces `&`, `<`, `>`, `"` and `'` with character references.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Html;
+
+impl Escaper for Html {
+    fn escape(&self, value: &str, write: &mut dyn Write) -> fmt::Result {
+        escape_chars(
+            value,
+            write,
+            |c| matches!(c, '&' | '<' | '>' | '"' | '\''),
+            |c, write| {
+                write.write_str(match c {
+                    '&' => "&amp;",
+                    '<' => "&lt;",
+                    '>' => "&gt;",
+                    '"' => "&quot;",
+                    _ => "&#x27;",
+                })
+            },
+        )
+    }
+}
+
+/// Escapes text for use inside a JSON string.
+///
+/// The surrounding quotes are not written, so the format string should contain them,
+/// e.g. `{{"name": "{name}"}}`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Json;
+
+impl Escaper for Json {
+    fn escape(&self, value: &str, write: &mut dyn Write) -> fmt::Result {
+        escape_chars(
+            value,
+            write,
+            |c| matches!(c, '"' | '\\') || (c as u32) < 0x20,
+            |c, write| match c {
+                '"' => write.write_str("\\\""),
+                '\\' => write.write_str("\\\\"),
+                '\n' => write.write_str("\\n"),
+                '\r' => write.write_str("\\r"),
+                '\t' => write.write_str("\\t"),
+                '\u{8}' => write.write_str("\\b"),
+                '\u{c}' => write.write_str("\\f"),
+                c => write!(write, "\\u{:04x}", c as u32),
+            },
+        )
+    }
+}
+
+/// Quotes text as a single POSIX shell word.
+///
+/// The value is wrapped in single quotes, and single quotes inside the value are written
+/// as `'\''`. The format string should not quote the variable itself.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Shell;
+
+impl Escaper for Shell {
+    fn escape(&self, value: &str, write: &mut dyn Write) -> fmt::Result {
+        write.write_char('\'')?;
+        escape_chars(
+            value,
+            write,
+            |c| c == '\'',
+            |_, write| write.write_str("'\\''"),
+        )?;
+        write.write_char('\'')
+    }
+}
+
+/// Escapes text as a CSV field, as described in RFC 4180.
+///
+/// Values that contain a comma, a double quote or a line break are wrapped in double
+/// quotes, and double quotes inside them are doubled. Other values are written as is.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Csv;
+
+impl Escaper for Csv {
+    fn escape(&self, value: &str, write: &mut dyn Write) -> fmt::Result {
+        if !value.contains([',', '"', '\n', '\r']) {
+            return write.write_str(value);
+        }
+
+        write.write_char('"')?;
+        escape_chars(
+            value,
+            write,
+            |c| c == '"',
+            |_, write| write.write_str("\"\""),
+        )?;
+        write.write_char('"')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Csv, Escaper, Html, Json, Shell};
+    use core::fmt::{self, Write};
+    use std::{
+        string::{String, ToString},
+        vec::Vec,
+    };
+
+    fn escape(escaper: &dyn Escaper, value: &str) -> String {
+        let mut escaped = String::new();
+
+        escaper.escape(value, &mut escaped).unwrap();
+
+        escaped
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape(&Html, r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#x27;s&lt;/a&gt;"
+        );
+        assert_eq!(escape(&Html, "plain ✓"), "plain ✓");
+    }
+
+    #[test]
+    fn test_escape_writes_runs() {
+        struct Writes(Vec<String>);
+
+        impl Write for Writes {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.0.push(s.to_string());
+                Ok(())
+            }
+        }
+
+        let mut writes = Writes(Vec::new());
+
+        Html.escape("<b>Tom & Jerry</b>", &mut writes).unwrap();
+
+        assert_eq!(
+            writes.0,
+            ["&lt;", "b", "&gt;", "Tom ", "&amp;", " Jerry", "&lt;", "/b", "&gt;"]
+        );
+    }
+
+    #[test]
+    fn test_escape_json() {
+        assert_eq!(
+            escape(&Json, "say \"hi\"\\\n\t\u{1}é"),
+            "say \\\"hi\\\"\\\\\\n\\t\\u0001é"
+        );
+    }
+
+    #[test]
+    fn test_escape_shell() {
+        assert_eq!(
+            escape(&Shell, "it's $HOME; rm -rf /"),
+            "'it'\\''s $HOME; rm -rf /'"
+        );
+        assert_eq!(escape(&Shell, ""), "''");
+    }
+
+    #[test]
+    fn test_escape_csv() {
+        assert_eq!(escape(&Csv, "plain"), "plain");
+        assert_eq!(escape(&Csv, "a,b"), "\"a,b\"");
+        assert_eq!(escape(&Csv, "say \"hi\"\nbye"), "\"say \"\"hi\"\"\nbye\"");
+    }
+}
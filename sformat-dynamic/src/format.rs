@@ -1,29 +1,90 @@
 use crate::{
     context::{Sign, TypedValue},
+    escape::{self, Escaper},
     Name,
 };
-use std::{
-    io::{self, Write},
-    ops::Range,
-};
+use alloc::{borrow::Cow, string::String};
+use core::fmt::{self, Write};
+#[cfg(feature = "std")]
+use std::io;
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug)]
 pub enum Error<'a> {
-    #[error("error writing literal: {0}")]
+    #[cfg(feature = "std")]
     WriteLiteralError(io::Error),
-
-    #[error("error writing variable({0}: {1}")]
+    #[cfg(feature = "std")]
     WriteVariableError(Name<'a>, io::Error),
-
-    #[error("error finding name: {0}")]
+    FmtError(fmt::Error),
     VariableNameError(Name<'a>),
-
-    #[error("variable ({0}) had incorrect type")]
     VariableTypeError(Name<'a>),
+    OutputLimitError(usize),
+    IncludeError(Name<'a>),
+}
+
+// `Display` is implemented by hand, rather than derived with `thiserror`, so errors can
+// be reported without the `std` feature.
+impl fmt::Display for Error<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            Error::WriteLiteralError(err) => write!(f, "error writing literal: {}", err),
+            #[cfg(feature = "std")]
+            Error::WriteVariableError(name, err) => {
+                write!(f, "error writing variable ({}): {}", name, err)
+            }
+            Error::FmtError(err) => write!(f, "error writing formatted output: {}", err),
+            Error::VariableNameError(name) => write!(f, "error finding name: {}", name),
+            Error::VariableTypeError(name) => {
+                write!(f, "variable ({}) had incorrect type", name)
+            }
+            Error::OutputLimitError(limit) => {
+                write!(f, "formatted output exceeds the limit of {} bytes", limit)
+            }
+            Error::IncludeError(name) => write!(
+                f,
+                "template include ({}) can only be rendered from a registry",
+                name
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error<'_> {}
+
+/// Adapts an `io::Write` to `fmt::Write`.
+///
+/// `fmt::Error` doesn't carry any information, so the underlying `io::Error` is kept
+/// around to be reported instead.
+#[cfg(feature = "std")]
+pub(crate) struct IoWriter<'w, W> {
+    inner: &'w mut W,
+    pub(crate) error: Option<io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<'w, W> IoWriter<'w, W> {
+    pub(crate) fn new(inner: &'w mut W) -> IoWriter<'w, W> {
+        IoWriter { inner, error: None }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'w, W> Write for IoWriter<'w, W>
+where
+    W: io::Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
 }
 
 pub const ZERO_FILL: Fill = Fill::new(Some('0'), Alignment::Right);
 pub const DEFAULT_FILL: Fill = Fill::new(Some(' '), Alignment::Left);
+pub const DEFAULT_NUMERIC_FILL: Fill = Fill::new(Some(' '), Alignment::Right);
 
 /// The `SignFlag` can be specified to always print the sign of a number.
 ///
@@ -50,7 +111,7 @@ impl SignFlag {
     }
 }
 
-#[derive(Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub struct Flags {
     sign: Option<SignFlag>,
 
@@ -63,12 +124,16 @@ impl Flags {
         Flags { sign, zero }
     }
 
-    fn is_number_aware(&self) -> bool {
+    pub(crate) fn is_number_aware(&self) -> bool {
         self.zero.is_some()
     }
+
+    pub(crate) fn has_plus_sign(&self) -> bool {
+        self.sign == Some(SignFlag::Plus)
+    }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct Format {
     fill: Option<Fill>,
     flags: Flags,
@@ -91,118 +156,181 @@ impl Format {
         }
     }
 
-    fn get_fill(&self, val: &TypedValue<'_>) -> Fill {
-        if self.flags.is_number_aware() && val.is_numeric() {
-            ZERO_FILL
-        } else {
-            self.fill.unwrap_or(DEFAULT_FILL)
-        }
+    pub(crate) fn width(&self) -> Option<u32> {
+        self.width
     }
 
-    fn write_sign<W>(&self, sign: Sign, write: &mut W) -> Result<Option<Sign>, io::Error>
-    where
-        W: Write,
-    {
-        match sign {
-            sign @ Sign::Positive | sign @ Sign::Zero => {
-                write.write_all(&[sign.into()])?;
+    pub(crate) fn precision(&self) -> Option<u32> {
+        self.precision
+    }
 
-                Ok(Some(sign))
-            }
-            sign @ Sign::Negative if self.flags.is_number_aware() => {
-                write.write_all(&[sign.into()])?;
+    pub(crate) fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    /// Returns the explicit alignment, if there is one.
+    pub(crate) fn alignment(&self) -> Option<Alignment> {
+        self.fill.map(|fill| fill.alignment)
+    }
+
+    pub(crate) fn fill_char(&self) -> char {
+        self.fill
+            .map_or(' ', |fill| fill.get_fill_char_or_default())
+    }
 
-                Ok(Some(sign))
+    fn get_fill(&self, val: &TypedValue<'_>) -> Fill {
+        match self.fill {
+            Some(fill) => fill,
+            None if val.is_numeric() => DEFAULT_NUMERIC_FILL,
+            None => DEFAULT_FILL,
+        }
+    }
+
+    /// Splits the sign from the string representation of `val`.
+    ///
+    /// The sign is split from the rest of the value, because zero padding goes between
+    /// them. A minus sign is already part of the string representation, and always takes
+    /// precedence over the plus flag (e.g. for `-0.0`).
+    fn split_sign<'r>(&self, val: TypedValue<'_>, repr: &'r str) -> (Option<char>, &'r str) {
+        match repr.strip_prefix('-') {
+            Some(rest) if val.is_numeric() => (Some('-'), rest),
+            _ => {
+                let sign = self
+                    .flags
+                    .sign
+                    .and_then(|sign_flag| sign_flag.get_sign_for_value(val))
+                    .filter(|sign| !matches!(sign, Sign::Negative))
+                    .map(char::from);
+
+                (sign, repr)
             }
-            // Negative Flag is written as a part of the string
-            // representation already.
-            _ => Ok(None),
         }
     }
 
+    /// Returns the number of fill characters written around a value.
+    fn padding(&self, sign: Option<char>, val_str: &str) -> usize {
+        // Width is measured in characters, like `std::fmt`.
+        let len = usize::from(sign.is_some()) + val_str.chars().count();
+
+        self.width
+            .map_or(0, |width| (width as usize).saturating_sub(len))
+    }
+
+    /// Returns the number of bytes `write_formatted` writes for `val`, without writing the
+    /// padding anywhere.
+    pub(crate) fn formatted_len(&self, val: TypedValue<'_>) -> usize {
+        let repr = val.string_repr(self.precision);
+        let (sign, val_str) = self.split_sign(val, repr.as_ref());
+        let fill_char = if self.flags.is_number_aware() && val.is_numeric() {
+            '0'
+        } else {
+            self.get_fill(&val).get_fill_char_or_default()
+        };
+
+        self.padding(sign, val_str)
+            .saturating_mul(fill_char.len_utf8())
+            .saturating_add(sign.map_or(0, char::len_utf8) + val_str.len())
+    }
+
+    /// Writes `val`, escaping it with `escaper` if there is one. The fill is written as is,
+    /// and so is the sign of a zero padded number, which is written before the padding.
     pub fn write_formatted<'a, W>(
         &self,
         val: TypedValue<'a>,
+        escaper: Option<&dyn Escaper>,
         write: &mut W,
-    ) -> Result<(), io::Error>
+    ) -> Result<(), fmt::Error>
     where
-        W: Write,
+        W: Write + ?Sized,
     {
-        let write_str = val.string_repr(self.precision);
-        let mut write_str = write_str.as_ref();
-        let sign = self
-            .flags
-            .sign
-            .and_then(|sign_flag| sign_flag.get_sign_for_value(val));
+        let repr = val.string_repr(self.precision);
+        let (sign, val_str) = self.split_sign(val, repr.as_ref());
+        let padding = self.padding(sign, val_str);
 
-        match self.width {
-            // There is a width specified, but the string that is being written
-            // is actually larger than what is specified.
-            Some(width) if write_str.len() > width as usize => {
-                if let Some(sign) = sign {
-                    self.write_sign(sign, write)?;
-                }
-
-                write.write_all(write_str.as_bytes())?;
+        if self.flags.is_number_aware() && val.is_numeric() {
+            // For a number aware (zero-flag) format, the sign is written before the
+            // padding, and the alignment is ignored.
+            if let Some(sign) = sign {
+                write.write_char(sign)?;
             }
-            // No width is specified.
-            None => {
-                if let Some(sign) = sign {
-                    self.write_sign(sign, write)?;
-                }
 
-                write.write_all(write_str.as_bytes())?;
-            }
-            // A width is specified. Implicit: The string that is being written
-            // is smaller than the specified width.
-            Some(width) => {
-                let mut width = width as usize;
-                let fill = self.get_fill(&val);
-                if self.flags.is_number_aware() {
-                    // For a number aware (zero-flag) format, the sign has to be written
-                    // first. This means that for a negative number, the string that
-                    // gets written has to be truncated slightly.
-                    match sign
-                        .map(|sign| self.write_sign(sign, write))
-                        .transpose()?
-                        .flatten()
-                    {
-                        Some(Sign::Positive) | Some(Sign::Zero) => {
-                            width -= 1;
-                        }
-                        Some(Sign::Negative) => {
-                            width -= 1;
-                            write_str = &write_str[1..];
-                        }
-                        _ => (),
-                    }
-                } else {
-                    // If the zero-flag is not specified, writing the "+" sign is
-                    // deferred until after the filler is written, but it should still
-                    // be considered when the left filler.
-                    match sign {
-                        Some(Sign::Positive) | Some(Sign::Zero) => {
-                            width -= 1;
-                        }
-                        _ => (),
-                    }
-                }
+            ZERO_FILL.write_filler(padding, write)?;
+            escape::write_escaped(val_str, escaper, write)
+        } else {
+            let fill = self.get_fill(&val);
+            let (left_pad, right_pad) = fill.split_padding(padding);
+
+            fill.write_filler(left_pad, write)?;
 
-                width -= fill.write_left_filler(write_str, width, write)?;
+            // The sign is escaped along with the value, since some escapers quote the
+            // whole value. Only then does a plus sign need a copy of the value.
+            match (sign, escaper) {
+                (Some('+'), Some(escaper)) => {
+                    let mut signed = String::with_capacity(val_str.len() + 1);
 
-                if !self.flags.is_number_aware() {
-                    sign.map(|sign| self.write_sign(sign, write)).transpose()?;
+                    signed.push('+');
+                    signed.push_str(val_str);
+                    escape::write_escaped(&signed, Some(escaper), write)?;
                 }
+                (Some('+'), None) => {
+                    write.write_char('+')?;
+                    write.write_str(val_str)?;
+                }
+                // A minus sign is part of the string representation.
+                (Some(_), _) => escape::write_escaped(repr.as_ref(), escaper, write)?,
+                (None, _) => escape::write_escaped(val_str, escaper, write)?,
+            }
+
+            fill.write_filler(right_pad, write)
+        }
+    }
 
-                write.write_all(write_str.as_bytes())?;
+    /// Removes the padding `write_formatted` would have added to a value.
+    ///
+    /// Padding is only added to values shorter than the width, so nothing is removed from
+    /// a value that is longer. Without an explicit alignment the value could be a string or
+    /// a number, so fill is removed from both sides.
+    pub(crate) fn strip_padding<'s>(&self, formatted: &'s str) -> Cow<'s, str> {
+        match self.width {
+            Some(width) if formatted.chars().count() == width as usize => (),
+            _ => return Cow::Borrowed(formatted),
+        }
 
-                width -= write_str.len();
+        let stripped = match self.fill {
+            Some(fill) => {
+                let fill_char = fill.get_fill_char_or_default();
 
-                fill.write_right_filler(width, write)?;
+                match fill.alignment {
+                    Alignment::Left => formatted.trim_end_matches(fill_char),
+                    Alignment::Center => formatted.trim_matches(fill_char),
+                    Alignment::Right => formatted.trim_start_matches(fill_char),
+                }
             }
+            None => formatted.trim_matches(' '),
+        };
+
+        if !self.flags.is_number_aware() {
+            return Cow::Borrowed(stripped);
         }
 
-        Ok(())
+        // Zero padding goes between the sign and the digits, and at least one digit before
+        // the decimal point is kept.
+        let (sign, digits) = match stripped.strip_prefix(['-', '+']) {
+            Some(digits) => (&stripped[..1], digits),
+            None => ("", stripped),
+        };
+        let zeros = digits
+            .char_indices()
+            .take_while(|&(i, c)| {
+                c == '0' && digits[i + 1..].starts_with(|c: char| c.is_ascii_digit())
+            })
+            .count();
+
+        match (sign, zeros) {
+            (_, 0) => Cow::Borrowed(stripped),
+            ("", _) => Cow::Borrowed(&digits[zeros..]),
+            (sign, _) => Cow::Owned([sign, &digits[zeros..]].concat()),
+        }
     }
 }
 
@@ -224,50 +352,23 @@ impl Fill {
         self.fill_char.unwrap_or(' ')
     }
 
-    fn write_filler<W>(&self, range: Range<usize>, write: &mut W) -> Result<usize, io::Error>
+    fn write_filler<W>(&self, count: usize, write: &mut W) -> Result<(), fmt::Error>
     where
-        W: Write,
+        W: Write + ?Sized,
     {
-        let mut buffer = [0; 4];
-        let bytes_to_write = range.len();
-        for _ in range {
-            let encoded = self.get_fill_char_or_default().encode_utf8(&mut buffer);
-
-            write.write_all(encoded.as_bytes())?;
+        for _ in 0..count {
+            write.write_char(self.get_fill_char_or_default())?;
         }
 
-        Ok(bytes_to_write)
-    }
-
-    fn write_left_filler<W>(
-        &self,
-        val: &str,
-        width: usize,
-        write: &mut W,
-    ) -> Result<usize, io::Error>
-    where
-        W: Write,
-    {
-        match self.alignment {
-            // NOOP
-            Alignment::Left => Ok(0),
-            Alignment::Center => {
-                let left_pad = (width - val.len()) / 2;
-
-                self.write_filler(0..left_pad, write)
-            }
-            Alignment::Right => self.write_filler(0..width - val.len(), write),
-        }
+        Ok(())
     }
 
-    fn write_right_filler<W>(&self, width: usize, write: &mut W) -> Result<usize, io::Error>
-    where
-        W: Write,
-    {
+    /// Splits the padding into the amount written before and after the value.
+    fn split_padding(&self, padding: usize) -> (usize, usize) {
         match self.alignment {
-            Alignment::Left | Alignment::Center => self.write_filler(0..width, write),
-            // NOOP
-            Alignment::Right => Ok(0),
+            Alignment::Left => (0, padding),
+            Alignment::Center => (padding / 2, padding - padding / 2),
+            Alignment::Right => (padding, 0),
         }
     }
 }
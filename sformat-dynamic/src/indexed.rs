@@ -0,0 +1,20 @@
+use crate::{context::TypedValue, Name};
+
+/// A context with a fixed set of variables that can be looked up by index.
+///
+/// Names are resolved to indices once, with [`CompiledFormat::resolve`], so formatting
+/// the resolved format doesn't need to compare any strings. This is usually derived with
+/// `#[derive(IndexedContext)]`, which supports `#[context(flatten)]` like the `Context`
+/// derive.
+///
+/// [`CompiledFormat::resolve`]: crate::CompiledFormat::resolve
+pub trait IndexedContext {
+    /// The number of variables in the context.
+    const LEN: usize;
+
+    /// Returns the index of the variable with the given name, which is less than `LEN`.
+    fn variable_index(name: Name<'_>) -> Option<usize>;
+
+    /// Returns the variable at the given index.
+    fn get_indexed(&self, index: usize) -> Option<TypedValue<'_>>;
+}
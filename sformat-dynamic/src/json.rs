@@ -0,0 +1,144 @@
+use crate::{
+    context::{Context, ContextValue, DynPointer, TypedValue},
+    format, Name,
+};
+use serde_json::{Map, Value};
+
+/// Follows a dotted path (e.g. `user.emails.0`) through nested objects and arrays.
+fn get_path<'a>(value: &'a Value, mut path: std::str::Split<'_, char>) -> Option<&'a Value> {
+    path.try_fold(value, |value, segment| match value {
+        Value::Object(map) => map.get(segment),
+        Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+/// Converts a JSON leaf to a `TypedValue`.
+///
+/// Numbers map to the closest integer or float variant. Nulls, objects and arrays can't
+/// be formatted.
+fn typed_value(value: &Value) -> Option<TypedValue<'_>> {
+    match value {
+        Value::String(string) => Some(TypedValue::Str(string)),
+        Value::Bool(boolean) => Some(TypedValue::Bool(*boolean)),
+        Value::Number(number) => number
+            .as_u64()
+            .map(TypedValue::Uint64)
+            .or_else(|| number.as_i64().map(TypedValue::Int64))
+            .or_else(|| number.as_f64().map(TypedValue::Float64)),
+        Value::Null | Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+/// JSON values can be used as a context if they are objects. Nested objects and arrays
+/// are reachable through dotted names like `{user.emails.0}`.
+impl<'ctxt> Context<'ctxt> for Value {
+    fn get_variable<'b>(
+        &'ctxt self,
+        name: Name<'b>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'b>> {
+        match self {
+            Value::Object(map) => map.get_variable(name),
+            _ => Err(format::Error::VariableNameError(name)),
+        }
+    }
+}
+
+impl<'ctxt> Context<'ctxt> for Map<String, Value> {
+    fn get_variable<'b>(
+        &'ctxt self,
+        name: Name<'b>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'b>> {
+        let mut path = name.split('.');
+        let value = path
+            .next()
+            .and_then(|key| self.get(key))
+            .and_then(|value| get_path(value, path))
+            .ok_or(format::Error::VariableNameError(name))?;
+
+        typed_value(value).ok_or(format::Error::VariableTypeError(name))
+    }
+}
+
+/// JSON values can be stored in other contexts, e.g. a `BTreeMap<String, Value>`. Nulls,
+/// objects and arrays are formatted as JSON text, since there's no path to follow into them.
+impl<'ctxt> ContextValue<'ctxt> for Value {
+    fn typed_value(&'ctxt self) -> TypedValue<'ctxt> {
+        typed_value(self).unwrap_or(TypedValue::Dyn(DynPointer::Display(self)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compile, format};
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_json_context() {
+        let context = json!({
+            "name": "Ferris",
+            "age": 7,
+            "balance": -12,
+            "ratio": 0.25,
+            "admin": false,
+        });
+        let formatted = compile("{name} {age:03} {balance:+} {ratio:.3} {admin}")
+            .unwrap()
+            .format_str(&context);
+
+        assert_eq!(formatted.unwrap(), "Ferris 007 -12 0.250 false");
+    }
+
+    #[test]
+    fn test_json_context_nested() {
+        let context = json!({
+            "user": {
+                "emails": ["ferris@example.com", "crab@example.com"]
+            }
+        });
+        let formatted = compile("{user.emails.1}").unwrap().format_str(&context);
+
+        assert_eq!(formatted.unwrap(), "crab@example.com");
+    }
+
+    #[test]
+    fn test_json_context_errors() {
+        let context = json!({ "user": { "name": null }, "items": [] });
+        let format = compile("{user.name}").unwrap();
+
+        assert!(matches!(
+            format.format_str(&context),
+            Err(format::Error::VariableTypeError("user.name"))
+        ));
+
+        let format = compile("{user}").unwrap();
+
+        assert!(matches!(
+            format.format_str(&context),
+            Err(format::Error::VariableTypeError("user"))
+        ));
+
+        let format = compile("{items.0}").unwrap();
+
+        assert!(matches!(
+            format.format_str(&context),
+            Err(format::Error::VariableNameError("items.0"))
+        ));
+    }
+
+    #[test]
+    fn test_json_values_in_map() {
+        let context: BTreeMap<String, Value> = BTreeMap::from([
+            (String::from("name"), json!("Ferris")),
+            (String::from("age"), json!(7)),
+            (String::from("tags"), json!(["crab", "rust"])),
+            (String::from("owner"), Value::Null),
+        ]);
+        let formatted = compile("{name:>8} {age:03} {tags} {owner}")
+            .unwrap()
+            .format_str(&context);
+
+        assert_eq!(formatted.unwrap(), "  Ferris 007 [\"crab\",\"rust\"] null");
+    }
+}
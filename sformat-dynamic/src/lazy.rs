@@ -0,0 +1,131 @@
+use crate::{
+    context::{Context, ContextValue, OwnedValue, TypedValue},
+    format, Name,
+};
+use alloc::{boxed::Box, collections::BTreeMap, string::String, vec::Vec};
+use core::cell::OnceCell;
+
+/// Wraps a context with variables that are expensive to compute.
+///
+/// Lazy variables are computed by a closure, and only if a format string references them.
+/// The values are cached in a [`LazyScope`], which is meant to be created for a single
+/// format call, so each closure runs at most once per format call and later calls see
+/// fresh values. Other variables are looked up in the wrapped context.
+///
+/// ```rust
+/// # #[cfg(feature = "std")] {
+/// use sformat_dynamic::{compile, context, LazyContext};
+///
+/// let context = LazyContext::new(context! { name: "Ferris" })
+///     .with("hostname", || String::from("crab-01"));
+/// let format = compile("{name}@{hostname}").unwrap();
+///
+/// assert_eq!(format.format_str(&context.scope()).unwrap(), "Ferris@crab-01");
+/// # }
+/// ```
+pub struct LazyContext<'a, C> {
+    inner: C,
+    // Index of each lazy variable's closure in `compute`.
+    names: BTreeMap<String, usize>,
+    compute: Vec<Box<dyn Fn() -> OwnedValue + 'a>>,
+}
+
+impl<'a, C> LazyContext<'a, C> {
+    pub fn new(inner: C) -> LazyContext<'a, C> {
+        LazyContext {
+            inner,
+            names: BTreeMap::new(),
+            compute: Vec::new(),
+        }
+    }
+
+    /// Adds a variable that is computed by `compute` the first time it's needed in a
+    /// scope. This shadows a variable with the same name in the wrapped context.
+    pub fn with<F, V>(mut self, name: impl Into<String>, compute: F) -> LazyContext<'a, C>
+    where
+        F: Fn() -> V + 'a,
+        V: Into<OwnedValue>,
+    {
+        let compute: Box<dyn Fn() -> OwnedValue + 'a> = Box::new(move || compute().into());
+        let name = name.into();
+
+        match self.names.get(&name) {
+            Some(&index) => self.compute[index] = compute,
+            None => {
+                self.names.insert(name, self.compute.len());
+                self.compute.push(compute);
+            }
+        }
+
+        self
+    }
+
+    /// Starts a scope, usually for a single format call. Values computed in the scope are
+    /// dropped with it.
+    pub fn scope(&self) -> LazyScope<'_, 'a, C> {
+        LazyScope {
+            context: self,
+            values: self.compute.iter().map(|_| OnceCell::new()).collect(),
+        }
+    }
+}
+
+/// The values of a [`LazyContext`] computed during one format call.
+///
+/// See [`LazyContext::scope`].
+pub struct LazyScope<'s, 'a, C> {
+    context: &'s LazyContext<'a, C>,
+    values: Vec<OnceCell<OwnedValue>>,
+}
+
+impl<'ctxt, C> Context<'ctxt> for LazyScope<'_, '_, C>
+where
+    C: Context<'ctxt>,
+{
+    fn get_variable<'b>(
+        &'ctxt self,
+        name: Name<'b>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'b>> {
+        match self.context.names.get(name) {
+            Some(&index) => Ok(self.values[index]
+                .get_or_init(&self.context.compute[index])
+                .typed_value()),
+            None => self.context.inner.get_variable(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LazyContext;
+    use crate::{compile, context::TypedValue};
+    use std::{cell::Cell, collections::BTreeMap};
+
+    #[test]
+    fn test_lazy_context_only_computes_referenced_values() {
+        let computed = Cell::new(0);
+        let inner = BTreeMap::from([("name", TypedValue::Str("Ferris"))]);
+        let context = LazyContext::new(inner).with("expensive", || {
+            computed.set(computed.get() + 1);
+            format!("computed {} time(s)", computed.get())
+        });
+
+        let format = compile("{name}").unwrap();
+
+        assert_eq!(format.format_str(&context.scope()).unwrap(), "Ferris");
+        assert_eq!(computed.get(), 0);
+
+        let format = compile("{expensive}, {expensive:>20}").unwrap();
+
+        // The value is computed once per scope, however often the format references it.
+        assert_eq!(
+            format.format_str(&context.scope()).unwrap(),
+            "computed 1 time(s),   computed 1 time(s)"
+        );
+        assert_eq!(
+            format.format_str(&context.scope()).unwrap(),
+            "computed 2 time(s),   computed 2 time(s)"
+        );
+        assert_eq!(computed.get(), 2);
+    }
+}
@@ -38,7 +38,16 @@
 //! You can also use a `HashMap` when specifying the context, although it is significantly
 //! more verbose.
 //!
+//! Maps and slices of pairs with string keys (`HashMap`, `BTreeMap`, `&[(K, V)]`, and `IndexMap`
+//! with the `indexmap` feature) all implement `Context`. Values can be `TypedValue`s, strings
+//! or primitives.
+//!
+//! With the `json` feature, `serde_json::Value` objects can be used as a context too. Nested
+//! objects and arrays are reachable through dotted names like `{user.emails.0}`. JSON values
+//! can also be stored in maps, e.g. a `BTreeMap<String, serde_json::Value>`.
+//!
 //! ```rust
+//! # #[cfg(feature = "std")] {
 //! use sformat_dynamic::{compile, TypedValue};
 //! use std::collections::HashMap;
 //!
@@ -49,7 +58,110 @@
 //! let formatted = format.format_str(&context).unwrap();
 //!
 //! assert_eq!(formatted, "Hello           Ferris!")
+//! # }
 //! ```
+//!
+//! The [`context!`] macro builds the same map with less ceremony, converting each value
+//! with `TypedValue::from`.
+//!
+//! ```rust
+//! # #[cfg(feature = "std")] {
+//! use sformat_dynamic::{compile, context};
+//!
+//! let context = context! { name: "Ferris", age: 7 };
+//! let format = compile("{name} is {age}").unwrap();
+//! let formatted = format.format_str(&context).unwrap();
+//!
+//! assert_eq!(formatted, "Ferris is 7")
+//! # }
+//! ```
+//!
+//! Contexts can be combined without copying their values into a single map.
+//! [`Layered`] looks up variables in one context before falling back to another,
+//! [`Prefixed`] resolves dotted names like `{env.HOME}` from a nested context, and
+//! [`FnContext`] resolves variables with a closure.
+//!
+//! ```rust
+//! # #[cfg(feature = "std")] {
+//! use sformat_dynamic::{compile, Layered, Prefixed, TypedValue};
+//! use std::collections::HashMap;
+//!
+//! let global = HashMap::from([("name", TypedValue::Str("Ferris"))]);
+//! let env = HashMap::from([("HOME", TypedValue::Str("/home/ferris"))]);
+//! let context = Layered(&global, Prefixed("env.", &env));
+//! let format = compile("{name} lives in {env.HOME}").unwrap();
+//! let formatted = format.format_str(&context).unwrap();
+//!
+//! assert_eq!(formatted, "Ferris lives in /home/ferris")
+//! # }
+//! ```
+//!
+//! ### Escaping
+//!
+//! Values that end up in HTML, JSON, shell commands or CSV files should be escaped. Pass an
+//! [`Escaper`] to `CompiledFormat::format_escaped`, `format_fmt_escaped` or
+//! `format_str_escaped`, and it's applied to the value of every variable, but not to the
+//! literals of the format string or to fill and padding. The [`escape`] module has escapers
+//! for HTML, JSON strings, POSIX shell words and CSV fields.
+//!
+//! ```rust
+//! # #[cfg(feature = "std")] {
+//! use sformat_dynamic::{compile, context, escape};
+//!
+//! let context = context! { file: "it's here.txt" };
+//! let format = compile("cat {file}").unwrap();
+//! let formatted = format.format_str_escaped(&context, &escape::Shell).unwrap();
+//!
+//! assert_eq!(formatted, r"cat 'it'\''s here.txt'")
+//! # }
+//! ```
+//!
+//! ### Limits
+//!
+//! A format string from an untrusted source can ask for a huge width, like `{x:4294967295}`.
+//! By default, `compile` caps the width, the precision and the number of tokens, and the
+//! number of bytes written by each format call is capped at 1 MiB. [`compile_with_limits`]
+//! takes a [`Limits`] that changes those caps. Exceeding a limit returns an error.
+//!
+//! ### Caching
+//!
+//! A `CompiledFormat` borrows its format string. `CompiledFormat::into_owned` returns an
+//! `OwnedFormat`, which doesn't, and [`TemplateCache`] keeps the formats compiled from the
+//! format strings it has seen, evicting the least recently used once it's full.
+//!
+//! ### Registries
+//!
+//! A [`Registry`] holds named templates that can include each other with `{>name}`. Included
+//! templates are rendered with the same context, and includes of unknown templates or include
+//! cycles are reported when the registry is built.
+//!
+//! A [`DirectoryLoader`] builds a registry from the `*.fmt` files in a directory, and reloads
+//! it when asked. A template that can't be read or compiled keeps its last good version, and
+//! every error is reported with the path of its file.
+//!
+//! ### Parsing
+//!
+//! [`CompiledFormat::parse`] goes the other way, extracting the values of the variables from
+//! a string the format produced, e.g. a file name. Padding is removed from the values, and
+//! they can be converted to a [`TypedValue`] with [`Captures::get_as`].
+//! [`CompiledFormat::to_regex`] and [`CompiledFormat::to_glob`] convert a format to a pattern,
+//! to find the strings or files it could have produced.
+//!
+//! ### Logging
+//!
+//! With the `log` feature, a `RecordContext` exposes a `log::Record` to format strings, and
+//! `FormatLogger` is a minimal logger that writes each record through a format string, e.g.
+//! `"{level:<5} {target}: {message}"`.
+//!
+//! ### `no_std`
+//!
+//! The crate supports `no_std` targets with an allocator. Disable the default `std` feature
+//! to build on `core` and `alloc`. Formatted output is then written to a `core::fmt::Write`
+//! with `CompiledFormat::format_fmt` or `CompiledFormat::format_str`. Writing to an
+//! `io::Write`, the `HashMap` and environment contexts, the `context!` macro, and the `serde`,
+//! `json` and `log` features all require `std`. Errors implement `Display` either way, and
+//! `std::error::Error` with `std`.
+//!
 //! ### Feature Parity
 //!
 //! Consult the [`str::fmt`](https://doc.rust-lang.org/std/fmt/) documentation for
@@ -68,7 +180,7 @@
 //! | Precision - Astrix   `.*`           | ❌          | ❌                       |
 //!
 //! ### Derive Types
-//! 
+//!
 //! A table of types that can be derived using `sformat-dynamic-derive`.
 //!
 //! | Type                                | Implemented | Future Plan to Implement |
@@ -76,12 +188,15 @@
 //! | &T : Debug                          | ❌          | ✅                       |
 //! | &T : Display                        | ❌          | ✅                       |
 //! | &str                                | ✅          | ✅                       |
+//! | char                                | ✅          | N/A                      |
 //! | isize                               | ✅          | N/A                      |
+//! | i128                                | ✅          | N/A                      |
 //! | i64                                 | ✅          | N/A                      |
 //! | i32                                 | ✅          | N/A                      |
 //! | i16                                 | ✅          | N/A                      |
 //! | i8                                  | ✅          | N/A                      |
 //! | usize                               | ✅          | N/A                      |
+//! | u128                                | ✅          | N/A                      |
 //! | u64                                 | ✅          | N/A                      |
 //! | u32                                 | ✅          | N/A                      |
 //! | u16                                 | ✅          | N/A                      |
@@ -89,24 +204,105 @@
 //! | f64                                 | ✅          | N/A                      |
 //! | f32                                 | ✅          | N/A                      |
 //! | bool                                | ✅          | N/A                      |
+//! | NonZero* (e.g. NonZeroU32)          | ✅          | N/A                      |
+//! | Duration                            | ✅          | N/A                      |
+//!
+//! Fields that implement `Context` themselves can be marked with `#[context(flatten)]`.
+//! Variables that don't match any other field are looked up in the flattened fields, in
+//! declaration order.
+//!
+//! ```rust,ignore
+//! #[derive(Context)]
+//! struct Common {
+//!   request_id: u64,
+//! }
+//!
+//! #[derive(Context)]
+//! struct RequestContext {
+//!   status: u16,
+//!   #[context(flatten)]
+//!   common: Common,
+//! }
+//! ```
+//!
+//! Formats that are used many times with the same struct can skip looking up variables by
+//! name. Derive `IndexedContext` instead, and resolve the names to field indices once with
+//! `CompiledFormat::resolve`.
+//!
+//! ```rust,ignore
+//! let format = compile("{status} {request_id}").unwrap();
+//! let resolved = format.resolve::<RequestContext>().unwrap();
+//! let formatted = resolved.format_str(&context).unwrap();
+//! ```
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![forbid(unsafe_code)]
 
+extern crate alloc;
+
+// The tests use `std` to run, even when the crate is built without it.
+#[cfg(all(test, not(feature = "std")))]
+#[macro_use]
+extern crate std;
+
+#[cfg(feature = "std")]
+mod cache;
+mod combinator;
 mod compile;
 mod context;
+#[cfg(feature = "std")]
+mod env;
+pub mod escape;
 mod format;
+mod indexed;
+mod lazy;
+mod limits;
+#[cfg(feature = "std")]
+mod loader;
+mod macros;
+mod parse;
+mod pattern;
+mod registry;
 mod token;
 
+#[cfg(feature = "json")]
+mod json;
+
+#[cfg(feature = "log")]
+mod logger;
+
 #[cfg(feature = "serde")]
 pub mod serde;
+#[cfg(feature = "serde")]
+mod serialize;
 
 pub type Name<'a> = &'a str;
 
-pub use compile::{compile, CompileError, CompiledFormat};
-pub use context::{Context, DynPointer, TypedValue};
+#[cfg(feature = "std")]
+pub use cache::TemplateCache;
+pub use combinator::{FnContext, Layered, Prefixed};
+pub use compile::{
+    compile, compile_with_limits, BoundFormat, CompileError, CompiledFormat, OwnedFormat,
+    ResolvedFormat,
+};
+pub use context::{Context, ContextValue, DynPointer, OwnedValue, TypedValue};
+#[cfg(feature = "std")]
+pub use env::EnvContext;
+pub use escape::Escaper;
 pub use format::Error as FormatError;
+pub use indexed::IndexedContext;
+pub use lazy::{LazyContext, LazyScope};
+pub use limits::Limits;
+#[cfg(feature = "std")]
+pub use loader::{DirectoryLoader, LoadError};
+#[cfg(feature = "log")]
+pub use logger::{FormatLogger, LoggerError, RecordContext};
+pub use parse::{Captures, Error as MatchError, ValueKind};
+pub use registry::{Registry, RegistryError};
+#[cfg(feature = "serde")]
+pub use serialize::{SerializeContext, SerializeError};
 
 #[cfg(feature = "derive")]
 pub mod derive {
-    pub use sformat_dynamic_derive::Context;
+    pub use sformat_dynamic_derive::{Context, IndexedContext};
 }
@@ -0,0 +1,152 @@
+use core::fmt::{self, Write};
+
+/// Limits on the resources a format string can use.
+///
+/// Format strings that come from an untrusted source can ask for a huge width or
+/// precision, like `{x:4294967295}`, which would take a long time to write or exhaust
+/// memory. The width, precision and token limits are checked by [`compile_with_limits`],
+/// and the output limit is checked while formatting.
+///
+/// The default limits cap width, precision and the number of tokens at `u16::MAX`, and the
+/// output of a single format call at 1 MiB. The caps on their own still allow 65535
+/// variables of width 65535, so the output limit is what bounds memory use. The token
+/// limit matters most for a [`Registry`], where a few templates that each include another
+/// one twice expand to an exponential number of tokens.
+///
+/// ```rust
+/// # #[cfg(feature = "std")] {
+/// use sformat_dynamic::{compile_with_limits, context, FormatError, Limits};
+///
+/// let limits = Limits::new().with_max_width(64).with_max_output_bytes(16);
+///
+/// assert!(compile_with_limits("{name:4294967295}", limits).is_err());
+///
+/// let format = compile_with_limits("Hello {name:>32}!", limits).unwrap();
+/// let context = context! { name: "Ferris" };
+///
+/// assert!(matches!(
+///     format.format_str(&context),
+///     Err(FormatError::OutputLimitError(16))
+/// ));
+/// # }
+/// ```
+///
+/// [`compile_with_limits`]: crate::compile_with_limits
+/// [`Registry`]: crate::Registry
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Limits {
+    max_width: u32,
+    max_precision: u32,
+    max_tokens: usize,
+    max_output_bytes: usize,
+}
+
+impl Limits {
+    /// Limits that don't restrict anything.
+    pub const UNLIMITED: Limits = Limits {
+        max_width: u32::MAX,
+        max_precision: u32::MAX,
+        max_tokens: usize::MAX,
+        max_output_bytes: usize::MAX,
+    };
+
+    /// Creates the default limits.
+    pub const fn new() -> Limits {
+        Limits {
+            max_width: u16::MAX as u32,
+            max_precision: u16::MAX as u32,
+            max_tokens: u16::MAX as usize,
+            max_output_bytes: 1 << 20,
+        }
+    }
+
+    /// Sets the largest width a variable can be formatted with, e.g. `{name:32}`.
+    pub const fn with_max_width(mut self, max_width: u32) -> Limits {
+        self.max_width = max_width;
+        self
+    }
+
+    /// Sets the largest precision a variable can be formatted with, e.g. `{ratio:.3}`.
+    pub const fn with_max_precision(mut self, max_precision: u32) -> Limits {
+        self.max_precision = max_precision;
+        self
+    }
+
+    /// Sets the largest number of tokens (literals and variables) a format string can
+    /// compile to.
+    pub const fn with_max_tokens(mut self, max_tokens: usize) -> Limits {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Sets the largest number of bytes a single format call can write.
+    pub const fn with_max_output_bytes(mut self, max_output_bytes: usize) -> Limits {
+        self.max_output_bytes = max_output_bytes;
+        self
+    }
+
+    pub const fn max_width(&self) -> u32 {
+        self.max_width
+    }
+
+    pub const fn max_precision(&self) -> u32 {
+        self.max_precision
+    }
+
+    pub const fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    pub const fn max_output_bytes(&self) -> usize {
+        self.max_output_bytes
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits::new()
+    }
+}
+
+/// Fails writes once more than a fixed number of bytes have been written.
+///
+/// A write that would go over the limit is rejected entirely, and `exceeded` is set so the
+/// `fmt::Error` can be told apart from an error in the underlying writer.
+pub(crate) struct LimitedWriter<'w, W: ?Sized> {
+    inner: &'w mut W,
+    remaining: usize,
+    pub(crate) exceeded: bool,
+}
+
+impl<'w, W: ?Sized> LimitedWriter<'w, W> {
+    pub(crate) fn new(inner: &'w mut W, max_bytes: usize) -> LimitedWriter<'w, W> {
+        LimitedWriter {
+            inner,
+            remaining: max_bytes,
+            exceeded: false,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn inner_mut(&mut self) -> &mut W {
+        self.inner
+    }
+}
+
+impl<W> Write for LimitedWriter<'_, W>
+where
+    W: Write + ?Sized,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self.remaining.checked_sub(s.len()) {
+            Some(remaining) => {
+                self.remaining = remaining;
+                self.inner.write_str(s)
+            }
+            None => {
+                self.exceeded = true;
+                Err(fmt::Error)
+            }
+        }
+    }
+}
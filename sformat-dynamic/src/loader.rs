@@ -0,0 +1,296 @@
+use crate::{
+    compile::{compile_with_limits, CompileError},
+    limits::Limits,
+    registry::{Registry, RegistryError},
+};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Error loading templates from a directory.
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+    #[error("error reading {}: {1}", .0.display())]
+    IoError(PathBuf, #[source] io::Error),
+
+    #[error("error compiling {}: {1}", .0.display())]
+    CompileError(PathBuf, #[source] CompileError),
+
+    #[error("error building registry: {0}")]
+    RegistryError(#[source] RegistryError),
+}
+
+/// Loads every `*.fmt` file in a directory into a [`Registry`].
+///
+/// Each template is named after its file, without the extension, so `header.fmt` is
+/// `header`, and can be included with `{>header}`. Call [`DirectoryLoader::reload`] to
+/// pick up changes on disk.
+///
+/// A template that fails to compile keeps its last good version, so a bad edit doesn't
+/// take it away. If the templates can't be combined into a registry, e.g. because of an
+/// include cycle, the previous registry is kept as a whole.
+///
+/// ```rust,no_run
+/// use sformat_dynamic::{context, DirectoryLoader};
+///
+/// let mut loader = DirectoryLoader::new("/etc/app/formats");
+///
+/// for err in loader.reload().err().unwrap_or_default() {
+///     eprintln!("{}", err);
+/// }
+///
+/// let registry = loader.registry();
+/// let context = context! { status: 200 };
+///
+/// if let Some(format) = registry.get("access") {
+///     println!("{}", format.format_str(&context).unwrap());
+/// }
+/// ```
+#[derive(Debug)]
+pub struct DirectoryLoader {
+    dir: PathBuf,
+    limits: Limits,
+    // The last version of each template that compiled.
+    sources: BTreeMap<String, String>,
+    registry: Arc<Registry>,
+}
+
+impl DirectoryLoader {
+    /// Creates a loader for `dir`. Nothing is read until [`DirectoryLoader::reload`] is
+    /// called.
+    pub fn new(dir: impl Into<PathBuf>) -> DirectoryLoader {
+        DirectoryLoader {
+            dir: dir.into(),
+            limits: Limits::new(),
+            sources: BTreeMap::new(),
+            registry: Arc::new(Registry::new::<_, String, String>([]).unwrap()),
+        }
+    }
+
+    /// Compiles templates with `limits` instead of the default limits.
+    pub fn with_limits(mut self, limits: Limits) -> DirectoryLoader {
+        self.limits = limits;
+        self
+    }
+
+    /// Reads and compiles every template in the directory.
+    ///
+    /// Every template that compiles replaces its previous version, a template that can't be
+    /// read or compiled keeps it, and templates whose file was removed are dropped. All problems are reported together. Even if there are
+    /// errors, the templates that did compile are updated.
+    pub fn reload(&mut self) -> Result<(), Vec<LoadError>> {
+        let mut errors = Vec::new();
+        let paths = match template_paths(&self.dir) {
+            Ok(paths) => paths,
+            Err(err) => return Err(vec![LoadError::IoError(self.dir.clone(), err)]),
+        };
+
+        let mut sources = BTreeMap::new();
+
+        for (name, path) in paths {
+            let error = match fs::read_to_string(&path) {
+                Ok(source) => match compile_with_limits(&source, self.limits) {
+                    Ok(_) => {
+                        sources.insert(name, source);
+                        continue;
+                    }
+                    Err(err) => LoadError::CompileError(path, err),
+                },
+                Err(err) => LoadError::IoError(path, err),
+            };
+
+            errors.push(error);
+
+            // Cloned rather than taken, in case the registry fails to build and this
+            // reload is discarded.
+            if let Some(last_good) = self.sources.get(&name).cloned() {
+                sources.insert(name, last_good);
+            }
+        }
+
+        match Registry::with_limits(&sources, self.limits) {
+            Ok(registry) => {
+                self.sources = sources;
+                self.registry = Arc::new(registry);
+            }
+            Err(err) => errors.push(LoadError::RegistryError(err)),
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Returns the templates from the last successful reload.
+    ///
+    /// The registry is shared, so a reload doesn't affect registries that were returned
+    /// before it.
+    pub fn registry(&self) -> Arc<Registry> {
+        Arc::clone(&self.registry)
+    }
+}
+
+/// Returns the name and path of every `*.fmt` file in `dir`, sorted by name.
+fn template_paths(dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut paths = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if !path.is_file() || path.extension() != Some("fmt".as_ref()) {
+            continue;
+        }
+
+        // Templates are referenced by name in includes, so names have to be unicode.
+        if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+            paths.push((name.to_owned(), path));
+        }
+    }
+
+    // `read_dir` doesn't guarantee an order, and errors should be reported in a stable one.
+    paths.sort();
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DirectoryLoader, LoadError};
+    use crate::context::TypedValue;
+    use std::{collections::HashMap, fs};
+
+    #[test]
+    fn test_directory_loader_keeps_last_good_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let context = HashMap::from([("name", TypedValue::Str("Ferris"))]);
+
+        fs::write(dir.path().join("header.fmt"), "== {name} ==").unwrap();
+        fs::write(dir.path().join("page.fmt"), "{>header}!").unwrap();
+        fs::write(dir.path().join("notes.txt"), "{ignored").unwrap();
+
+        let mut loader = DirectoryLoader::new(dir.path());
+
+        loader.reload().unwrap();
+
+        let registry = loader.registry();
+        let page = registry.get("page").unwrap();
+
+        assert_eq!(page.format_str(&context).unwrap(), "== Ferris ==!");
+        assert_eq!(registry.names().collect::<Vec<_>>(), ["header", "page"]);
+
+        // A bad edit keeps the last good version, while other edits are still picked up.
+        fs::write(dir.path().join("header.fmt"), "== {name").unwrap();
+        fs::write(dir.path().join("page.fmt"), "{>header}?").unwrap();
+
+        let errors = loader.reload().unwrap_err();
+
+        assert!(matches!(
+            &errors[..],
+            [LoadError::CompileError(path, _)] if path.ends_with("header.fmt")
+        ));
+
+        let registry = loader.registry();
+
+        assert_eq!(
+            registry.get("page").unwrap().format_str(&context).unwrap(),
+            "== Ferris ==?"
+        );
+
+        // The last good version survives a reload that fails to build the registry.
+        fs::write(dir.path().join("page.fmt"), "{>missing}").unwrap();
+
+        let errors = loader.reload().unwrap_err();
+
+        assert!(matches!(
+            &errors[..],
+            [LoadError::CompileError(..), LoadError::RegistryError(_)]
+        ));
+
+        fs::write(dir.path().join("page.fmt"), "{>header}?").unwrap();
+
+        assert_eq!(loader.reload().unwrap_err().len(), 1);
+        assert_eq!(
+            loader
+                .registry()
+                .get("page")
+                .unwrap()
+                .format_str(&context)
+                .unwrap(),
+            "== Ferris ==?"
+        );
+
+        // Fixing the template picks it up again.
+        fs::write(dir.path().join("header.fmt"), "-- {name} --").unwrap();
+        loader.reload().unwrap();
+
+        assert_eq!(
+            loader
+                .registry()
+                .get("page")
+                .unwrap()
+                .format_str(&context)
+                .unwrap(),
+            "-- Ferris --?"
+        );
+    }
+
+    #[test]
+    fn test_directory_loader_keeps_last_good_version_of_unreadable_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let context = HashMap::from([("name", TypedValue::Str("Ferris"))]);
+
+        fs::write(dir.path().join("header.fmt"), "== {name} ==").unwrap();
+        fs::write(dir.path().join("page.fmt"), "{>header}!").unwrap();
+
+        let mut loader = DirectoryLoader::new(dir.path());
+
+        loader.reload().unwrap();
+
+        // Templates must be UTF-8, so this can't be read, but the page that includes it
+        // still builds with the last good version.
+        fs::write(dir.path().join("header.fmt"), b"== {name} \xff==").unwrap();
+
+        let errors = loader.reload().unwrap_err();
+
+        assert!(matches!(
+            &errors[..],
+            [LoadError::IoError(path, _)] if path.ends_with("header.fmt")
+        ));
+        assert_eq!(
+            loader
+                .registry()
+                .get("page")
+                .unwrap()
+                .format_str(&context)
+                .unwrap(),
+            "== Ferris ==!"
+        );
+    }
+
+    #[test]
+    fn test_directory_loader_reports_every_error() {
+        let dir = tempfile::tempdir().unwrap();
+
+        fs::write(dir.path().join("a.fmt"), "{a").unwrap();
+        fs::write(dir.path().join("b.fmt"), "{b:99999999}").unwrap();
+        fs::write(dir.path().join("c.fmt"), "{>d}").unwrap();
+
+        let mut loader = DirectoryLoader::new(dir.path());
+        let errors = loader.reload().unwrap_err();
+
+        assert!(matches!(
+            &errors[..],
+            [
+                LoadError::CompileError(a, _),
+                LoadError::CompileError(b, _),
+                LoadError::RegistryError(_),
+            ] if a.ends_with("a.fmt") && b.ends_with("b.fmt")
+        ));
+        assert_eq!(loader.registry().names().count(), 0);
+    }
+}
@@ -0,0 +1,339 @@
+use crate::{
+    compile::{compile, CompileError, OwnedFormat},
+    context::{Context, DynPointer, TypedValue},
+    format,
+    token::Token,
+    Name,
+};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::{
+    io::{self, Write},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The variables a [`RecordContext`] defines.
+const VARIABLES: [&str; 7] = [
+    "level",
+    "target",
+    "module_path",
+    "file",
+    "line",
+    "message",
+    "timestamp",
+];
+
+/// Error creating a [`FormatLogger`].
+#[derive(Debug, thiserror::Error)]
+pub enum LoggerError {
+    #[error("error compiling log format: {0}")]
+    CompileError(#[source] CompileError),
+
+    #[error("log format references unknown variable ({0})")]
+    UnknownVariableError(String),
+
+    #[error("log format includes a template ({0}), which can't be rendered")]
+    IncludeError(String),
+}
+
+/// A context over a `log::Record`.
+///
+/// | Variable      | Value                                      |
+/// | ------------- | ------------------------------------------ |
+/// | `level`       | The level, e.g. `INFO`                     |
+/// | `target`      | The target, usually the module path        |
+/// | `module_path` | The module the record was logged from      |
+/// | `file`        | The source file the record was logged from |
+/// | `line`        | The line the record was logged from        |
+/// | `message`     | The formatted message                      |
+/// | `timestamp`   | Seconds since the Unix epoch, as a float   |
+///
+/// `module_path`, `file` and `line` are empty if the record doesn't have them.
+///
+/// ```rust
+/// use log::{Level, Record};
+/// use sformat_dynamic::{compile, RecordContext};
+///
+/// let format = compile("{level:<5} {target}: {message}").unwrap();
+/// let formatted = format.format_str(&RecordContext::new(
+///     &Record::builder()
+///         .level(Level::Warn)
+///         .target("app::db")
+///         .args(format_args!("{} retries left", 3))
+///         .build(),
+/// ))
+/// .unwrap();
+///
+/// assert_eq!(formatted, "WARN  app::db: 3 retries left");
+/// ```
+pub struct RecordContext<'a, 'r> {
+    record: &'a Record<'r>,
+    timestamp: f64,
+}
+
+impl<'a, 'r> RecordContext<'a, 'r> {
+    /// Creates a context for `record`, timestamped with the current time.
+    pub fn new(record: &'a Record<'r>) -> RecordContext<'a, 'r> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |since_epoch| since_epoch.as_secs_f64());
+
+        RecordContext::with_timestamp(record, timestamp)
+    }
+
+    /// Creates a context for `record` with a timestamp in seconds since the Unix epoch.
+    pub fn with_timestamp(record: &'a Record<'r>, timestamp: f64) -> RecordContext<'a, 'r> {
+        RecordContext { record, timestamp }
+    }
+}
+
+impl<'ctxt> Context<'ctxt> for RecordContext<'_, '_> {
+    fn get_variable<'b>(
+        &'ctxt self,
+        name: Name<'b>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'b>> {
+        let record = self.record;
+        let value = match name {
+            "level" => TypedValue::Str(record.level().as_str()),
+            "target" => TypedValue::Str(record.target()),
+            "module_path" => TypedValue::Str(record.module_path().unwrap_or("")),
+            "file" => TypedValue::Str(record.file().unwrap_or("")),
+            "line" => record
+                .line()
+                .map_or(TypedValue::Str(""), TypedValue::Uint32),
+            "message" => TypedValue::Dyn(DynPointer::Display(record.args())),
+            "timestamp" => TypedValue::Float64(self.timestamp),
+            _ => return Err(format::Error::VariableNameError(name)),
+        };
+
+        Ok(value)
+    }
+}
+
+/// A logger that writes each record through a format string.
+///
+/// Records are formatted with a [`RecordContext`], and written on their own line. The
+/// format string can only reference the variables a `RecordContext` defines and can't
+/// include other templates, which is checked when the logger is created. If a record
+/// still fails to format, e.g. because it's longer than the output limit, its message is
+/// written on its own instead, so the record isn't lost.
+///
+/// ```rust,no_run
+/// use sformat_dynamic::FormatLogger;
+///
+/// FormatLogger::new("{timestamp:.3} {level:<5} {target}: {message}")
+///     .unwrap()
+///     .with_level(log::LevelFilter::Debug)
+///     .init()
+///     .unwrap();
+///
+/// log::info!("listening on port {}", 8080);
+/// ```
+pub struct FormatLogger {
+    format: OwnedFormat,
+    level: LevelFilter,
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl FormatLogger {
+    /// Compiles `format`, and creates a logger that writes records at `Info` level and
+    /// above to stderr.
+    pub fn new(format: &str) -> Result<FormatLogger, LoggerError> {
+        let format = compile(format).map_err(LoggerError::CompileError)?;
+
+        if let Some(name) = format.variables().find(|name| !VARIABLES.contains(name)) {
+            return Err(LoggerError::UnknownVariableError(name.to_owned()));
+        }
+
+        if let Some(Token::Include(name)) = format
+            .tokens()
+            .find(|token| matches!(token, Token::Include(_)))
+        {
+            return Err(LoggerError::IncludeError(name.to_owned()));
+        }
+
+        Ok(FormatLogger {
+            format: format.into_owned(),
+            level: LevelFilter::Info,
+            writer: Mutex::new(Box::new(io::stderr())),
+        })
+    }
+
+    /// Only writes records at `level` and above.
+    pub fn with_level(mut self, level: LevelFilter) -> FormatLogger {
+        self.level = level;
+        self
+    }
+
+    /// Writes records to `writer` instead of stderr.
+    pub fn with_writer<W>(mut self, writer: W) -> FormatLogger
+    where
+        W: Write + Send + 'static,
+    {
+        self.writer = Mutex::new(Box::new(writer));
+        self
+    }
+
+    /// Sets this as the global logger, and sets the global maximum level to its level.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        log::set_max_level(self.level);
+        log::set_boxed_logger(Box::new(self))
+    }
+}
+
+impl Log for FormatLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let context = RecordContext::new(record);
+        let mut line = self
+            .format
+            .as_compiled()
+            .format_str(&context)
+            .unwrap_or_else(|_| record.args().to_string());
+
+        line.push('\n');
+
+        // A poisoned lock only means another thread panicked mid-write, so keep logging.
+        let mut writer = self.writer.lock().unwrap_or_else(|err| err.into_inner());
+        let _ = writer.write_all(line.as_bytes());
+    }
+
+    fn flush(&self) {
+        let mut writer = self.writer.lock().unwrap_or_else(|err| err.into_inner());
+        let _ = writer.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FormatLogger, LoggerError, RecordContext};
+    use crate::compile;
+    use log::{Level, LevelFilter, Log, Record};
+    use std::{
+        io::{self, Write},
+        sync::{Arc, Mutex},
+    };
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_context() {
+        let format =
+            compile("{timestamp:.1} [{level:^7}] {module_path} ({file}:{line:04}) {message}")
+                .unwrap();
+        let formatted = format
+            .format_str(&RecordContext::with_timestamp(
+                &Record::builder()
+                    .level(Level::Error)
+                    .target("app")
+                    .module_path(Some("app::db"))
+                    .file(Some("src/db.rs"))
+                    .line(Some(42))
+                    .args(format_args!("lost {}", "connection"))
+                    .build(),
+                1700000000.25,
+            ))
+            .unwrap();
+
+        assert_eq!(
+            formatted,
+            "1700000000.2 [ ERROR ] app::db (src/db.rs:0042) lost connection"
+        );
+
+        let format = compile("{module_path}|{file}|{line}|{message}").unwrap();
+        let formatted = format
+            .format_str(&RecordContext::new(
+                &Record::builder().args(format_args!("hi")).build(),
+            ))
+            .unwrap();
+
+        assert_eq!(formatted, "|||hi");
+
+        let format = compile("{thread}").unwrap();
+
+        assert!(format
+            .format_str(&RecordContext::new(
+                &Record::builder().args(format_args!("hi")).build(),
+            ))
+            .is_err());
+    }
+
+    #[test]
+    fn test_format_logger() {
+        let buffer = Buffer::default();
+        let logger = FormatLogger::new("{level:<5} {target}: {message}")
+            .unwrap()
+            .with_level(LevelFilter::Info)
+            .with_writer(buffer.clone());
+
+        for (level, message) in [(Level::Info, "started"), (Level::Debug, "hidden")] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target("app")
+                    .args(format_args!("{}", message))
+                    .build(),
+            );
+        }
+
+        logger.flush();
+
+        assert_eq!(
+            String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap(),
+            "INFO  app: started\n"
+        );
+    }
+
+    #[test]
+    fn test_format_logger_errors() {
+        assert!(matches!(
+            FormatLogger::new("{level} {thread}: {message}"),
+            Err(LoggerError::UnknownVariableError(name)) if name == "thread"
+        ));
+        assert!(matches!(
+            FormatLogger::new("{level"),
+            Err(LoggerError::CompileError(_))
+        ));
+        assert!(matches!(
+            FormatLogger::new("{>header} {message}"),
+            Err(LoggerError::IncludeError(name)) if name == "header"
+        ));
+
+        // A record that still fails to format is written as its message.
+        let buffer = Buffer::default();
+        let logger = FormatLogger::new("{level}: {message}")
+            .unwrap()
+            .with_writer(buffer.clone());
+        let message = "x".repeat(1 << 20);
+
+        logger.log(
+            &Record::builder()
+                .level(Level::Error)
+                .args(format_args!("{}", message))
+                .build(),
+        );
+
+        assert_eq!(
+            String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap(),
+            message + "\n"
+        );
+    }
+}
@@ -0,0 +1,70 @@
+/// Builds a `HashMap` context from `name: value` pairs.
+///
+/// Values are converted with `TypedValue::from`, so strings, integers, floats and bools
+/// can be used directly. Other values can be formatted through their `Display`
+/// implementation with `name: %value`, or their `Debug` implementation with
+/// `name: ?value`. These values are borrowed, so they have to outlive the context.
+///
+/// ```rust
+/// use sformat_dynamic::{compile, context};
+/// use std::net::Ipv4Addr;
+///
+/// let addr = Ipv4Addr::LOCALHOST;
+/// let context = context! {
+///     name: "Ferris",
+///     age: 7,
+///     addr: %addr,
+///     tags: ?["crab", "rust"],
+/// };
+/// let format = compile("{name} ({age}) at {addr} {tags}").unwrap();
+///
+/// assert_eq!(
+///     format.format_str(&context).unwrap(),
+///     "Ferris (7) at 127.0.0.1 [\"crab\", \"rust\"]"
+/// );
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! context {
+    (@entries [$($entries:tt)*]) => {
+        [$($entries)*]
+    };
+    (@entries [$($entries:tt)*] $name:ident : % $value:expr $(, $($rest:tt)*)?) => {
+        $crate::context!(
+            @entries [
+                $($entries)*
+                (
+                    stringify!($name),
+                    $crate::TypedValue::Dyn($crate::DynPointer::Display(&$value)),
+                ),
+            ]
+            $($($rest)*)?
+        )
+    };
+    (@entries [$($entries:tt)*] $name:ident : ? $value:expr $(, $($rest:tt)*)?) => {
+        $crate::context!(
+            @entries [
+                $($entries)*
+                (
+                    stringify!($name),
+                    $crate::TypedValue::Dyn($crate::DynPointer::Debug(&$value)),
+                ),
+            ]
+            $($($rest)*)?
+        )
+    };
+    (@entries [$($entries:tt)*] $name:ident : $value:expr $(, $($rest:tt)*)?) => {
+        $crate::context!(
+            @entries [
+                $($entries)*
+                (stringify!($name), $crate::TypedValue::from($value)),
+            ]
+            $($($rest)*)?
+        )
+    };
+    ($($tokens:tt)*) => {
+        ::std::collections::HashMap::<&'static str, $crate::TypedValue>::from(
+            $crate::context!(@entries [] $($tokens)*)
+        )
+    };
+}
@@ -0,0 +1,342 @@
+use crate::{context::TypedValue, token::Token, Name};
+use alloc::{borrow::Cow, vec::Vec};
+use core::fmt;
+
+/// Error extracting the variables from a string, or converting a format to a pattern.
+#[derive(Debug)]
+pub enum Error<'a> {
+    LiteralError(&'a str, usize),
+    TrailingInputError(usize),
+    AdjacentVariablesError(Name<'a>),
+    VariableWidthError(Name<'a>, usize),
+    RepeatedVariableError(Name<'a>),
+    IncludeError(Name<'a>),
+    VariableNameError(Name<'a>),
+    VariableTypeError(Name<'a>),
+}
+
+impl fmt::Display for Error<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::LiteralError(literal, at) => {
+                write!(f, "expected literal ({:?}) at byte {}", literal, at)
+            }
+            Error::TrailingInputError(at) => write!(f, "unexpected input at byte {}", at),
+            Error::AdjacentVariablesError(name) => write!(
+                f,
+                "variable ({}) is followed by another variable and has no width",
+                name
+            ),
+            Error::VariableWidthError(name, at) => write!(
+                f,
+                "input ended before the width of variable ({}) at byte {}",
+                name, at
+            ),
+            Error::RepeatedVariableError(name) => {
+                write!(f, "variable ({}) has different values", name)
+            }
+            Error::IncludeError(name) => write!(f, "template include ({}) can't be parsed", name),
+            Error::VariableNameError(name) => write!(f, "error finding name: {}", name),
+            Error::VariableTypeError(name) => {
+                write!(f, "variable ({}) had incorrect type", name)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error<'_> {}
+
+/// The kind of `TypedValue` a captured variable is converted to by [`Captures::get_as`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ValueKind {
+    Str,
+    Char,
+    Int,
+    Int128,
+    Int64,
+    Int32,
+    Int16,
+    Int8,
+    Uint,
+    Uint128,
+    Uint64,
+    Uint32,
+    Uint16,
+    Uint8,
+    Float32,
+    Float64,
+    Bool,
+}
+
+impl ValueKind {
+    /// Parses `value` as this kind, or returns `None` if it isn't valid.
+    fn convert(self, value: &str) -> Option<TypedValue<'_>> {
+        macro_rules! parse {
+            ($variant:ident) => {
+                value.parse().ok().map(TypedValue::$variant)
+            };
+        }
+
+        match self {
+            ValueKind::Str => Some(TypedValue::Str(value)),
+            ValueKind::Char => {
+                let mut chars = value.chars();
+
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(TypedValue::Char(c)),
+                    _ => None,
+                }
+            }
+            ValueKind::Int => parse!(Int),
+            ValueKind::Int128 => parse!(Int128),
+            ValueKind::Int64 => parse!(Int64),
+            ValueKind::Int32 => parse!(Int32),
+            ValueKind::Int16 => parse!(Int16),
+            ValueKind::Int8 => parse!(Int8),
+            ValueKind::Uint => parse!(Uint),
+            ValueKind::Uint128 => parse!(Uint128),
+            ValueKind::Uint64 => parse!(Uint64),
+            ValueKind::Uint32 => parse!(Uint32),
+            ValueKind::Uint16 => parse!(Uint16),
+            ValueKind::Uint8 => parse!(Uint8),
+            ValueKind::Float32 => parse!(Float32),
+            ValueKind::Float64 => parse!(Float64),
+            ValueKind::Bool => parse!(Bool),
+        }
+    }
+}
+
+/// The values of the variables captured by [`CompiledFormat::parse`], in the order they
+/// appear in the format string.
+///
+/// [`CompiledFormat::parse`]: crate::CompiledFormat::parse
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Captures<'format, 's> {
+    captures: Vec<(Name<'format>, Cow<'s, str>)>,
+}
+
+impl<'format, 's> Captures<'format, 's> {
+    /// Returns the value captured for `name`.
+    pub fn get(&self, name: Name<'_>) -> Option<&str> {
+        self.captures
+            .iter()
+            .find(|(captured, _)| *captured == name)
+            .map(|(_, value)| value.as_ref())
+    }
+
+    /// Returns the value captured for `name`, converted to `kind`.
+    pub fn get_as<'n>(&self, name: Name<'n>, kind: ValueKind) -> Result<TypedValue<'_>, Error<'n>> {
+        let value = self.get(name).ok_or(Error::VariableNameError(name))?;
+
+        kind.convert(value).ok_or(Error::VariableTypeError(name))
+    }
+
+    /// Iterates over the name and value of each captured variable. A variable that is used
+    /// more than once is only returned once.
+    pub fn iter(&self) -> impl Iterator<Item = (Name<'format>, &str)> + '_ {
+        self.captures
+            .iter()
+            .map(|(name, value)| (*name, value.as_ref()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.captures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.captures.is_empty()
+    }
+
+    /// Adds a captured value, checking it against an earlier capture of the same variable.
+    fn insert(&mut self, name: Name<'format>, value: Cow<'s, str>) -> Result<(), Error<'format>> {
+        match self.get(name) {
+            Some(captured) if captured != value => Err(Error::RepeatedVariableError(name)),
+            Some(_) => Ok(()),
+            None => {
+                self.captures.push((name, value));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Matches `input` against `tokens`. See [`CompiledFormat::parse`].
+///
+/// [`CompiledFormat::parse`]: crate::CompiledFormat::parse
+pub(crate) fn parse<'format, 's, I>(
+    tokens: I,
+    input: &'s str,
+) -> Result<Captures<'format, 's>, Error<'format>>
+where
+    I: IntoIterator<Item = Token<'format>>,
+{
+    let mut tokens = tokens.into_iter().peekable();
+    let mut captures = Captures::default();
+    let mut pos = 0;
+
+    while let Some(token) = tokens.next() {
+        let rest = &input[pos..];
+
+        match token {
+            Token::Literal(literal) => {
+                if !rest.starts_with(literal) {
+                    return Err(Error::LiteralError(literal, pos));
+                }
+
+                pos += literal.len();
+            }
+            Token::Variable(name, format) => {
+                // A variable ends where the next literal starts, and if the literal is
+                // missing, the input ended before it was found. Without a literal, the
+                // width is the only way to tell where it ends.
+                let len = match tokens.peek() {
+                    None => rest.len(),
+                    Some(Token::Literal(literal)) => rest
+                        .find(literal)
+                        .ok_or(Error::LiteralError(literal, input.len()))?,
+                    Some(_) => {
+                        let width = format
+                            .and_then(|format| format.width())
+                            .ok_or(Error::AdjacentVariablesError(name))?;
+
+                        rest.char_indices()
+                            .map(|(i, _)| i)
+                            .chain([rest.len()])
+                            .nth(width as usize)
+                            .ok_or(Error::VariableWidthError(name, pos))?
+                    }
+                };
+                let value = match format {
+                    Some(format) => format.strip_padding(&rest[..len]),
+                    None => Cow::Borrowed(&rest[..len]),
+                };
+
+                captures.insert(name, value)?;
+                pos += len;
+            }
+            Token::Include(name) => return Err(Error::IncludeError(name)),
+        }
+    }
+
+    if pos < input.len() {
+        return Err(Error::TrailingInputError(pos));
+    }
+
+    Ok(captures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, ValueKind};
+    use crate::{compile, context::TypedValue};
+    use std::{collections::BTreeMap, vec::Vec};
+
+    #[test]
+    fn test_parse_round_trip() {
+        let format = compile("{date}_{host:>8}_{seq:05}.log").unwrap();
+        let context = BTreeMap::from([
+            ("date", TypedValue::Str("2024-01-02")),
+            ("host", TypedValue::Str("crab")),
+            ("seq", TypedValue::Int32(-42)),
+        ]);
+        let formatted = format.format_str(&context).unwrap();
+
+        assert_eq!(formatted, "2024-01-02_    crab_-0042.log");
+
+        let captures = format.parse(&formatted).unwrap();
+
+        assert_eq!(
+            captures.iter().collect::<Vec<_>>(),
+            [("date", "2024-01-02"), ("host", "crab"), ("seq", "-42")]
+        );
+        assert!(matches!(
+            captures.get_as("seq", ValueKind::Int32),
+            Ok(TypedValue::Int32(-42))
+        ));
+        assert!(matches!(
+            captures.get_as("host", ValueKind::Uint8),
+            Err(Error::VariableTypeError("host"))
+        ));
+        assert!(matches!(
+            captures.get_as("missing", ValueKind::Str),
+            Err(Error::VariableNameError("missing"))
+        ));
+    }
+
+    #[test]
+    fn test_parse_strips_padding() {
+        let cases = [
+            ("{x:*<6}", "ab****", "ab"),
+            ("{x:*^6}", "**ab**", "ab"),
+            ("{x:*>6}", "****ab", "ab"),
+            ("{x:6}", "ab    ", "ab"),
+            ("{x:6}", "    12", "12"),
+            // Without an alignment, both sides are trimmed, even for a string.
+            ("{x:6}", "  ab  ", "ab"),
+            ("{x:<6}", "  ab  ", "  ab"),
+            ("{x:05}", "00000", "0"),
+            ("{x:06}", "0000.5", "0.5"),
+            ("{x:+05}", "+0007", "+7"),
+            // Values longer than the width weren't padded.
+            ("{x:*<2}", "ab**", "ab**"),
+            ("{x:02}", "0007", "0007"),
+        ];
+
+        for (format, input, expected) in cases {
+            let format = compile(format).unwrap();
+
+            assert_eq!(
+                format.parse(input).unwrap().get("x"),
+                Some(expected),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_adjacent_variables() {
+        let format = compile("{year:04}{month:02}{day}").unwrap();
+        let captures = format.parse("20240102").unwrap();
+
+        assert_eq!(captures.get("year"), Some("2024"));
+        assert_eq!(captures.get("month"), Some("1"));
+        assert_eq!(captures.get("day"), Some("02"));
+
+        assert!(matches!(
+            format.parse("202"),
+            Err(Error::VariableWidthError("year", 0))
+        ));
+        assert!(matches!(
+            compile("{a}{b}").unwrap().parse("ab"),
+            Err(Error::AdjacentVariablesError("a"))
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let format = compile("{name}-{id}.log").unwrap();
+
+        assert!(matches!(
+            format.parse("ferris_7.log"),
+            Err(Error::LiteralError("-", 12))
+        ));
+        assert!(matches!(
+            format.parse("ferris-7.txt"),
+            Err(Error::LiteralError(".log", 12))
+        ));
+        assert!(matches!(
+            compile("{id}.log").unwrap().parse("7.log.gz"),
+            Err(Error::TrailingInputError(5))
+        ));
+        assert!(matches!(
+            compile("{id}-{id}").unwrap().parse("7-8"),
+            Err(Error::RepeatedVariableError("id"))
+        ));
+        assert!(matches!(
+            compile("{>header}").unwrap().parse(""),
+            Err(Error::IncludeError("header"))
+        ));
+    }
+}
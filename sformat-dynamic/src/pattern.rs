@@ -0,0 +1,233 @@
+use crate::{
+    format::{Alignment, Format},
+    parse,
+    token::Token,
+};
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+
+/// Matches a number, including any decimals.
+const NUMBER: &str = r"[0-9]+(?:\.[0-9]+)?";
+
+/// Writes `literal` with the characters that have a meaning in a regex escaped.
+fn escape_regex(literal: &str, pattern: &mut String) {
+    for c in literal.chars() {
+        if r"\.+*?()|[]{}^$#&-~".contains(c) {
+            pattern.push('\\');
+        }
+
+        pattern.push(c);
+    }
+}
+
+/// Writes `literal` with the characters that have a meaning in a glob wrapped in brackets.
+fn escape_glob(literal: &str, pattern: &mut String) {
+    for c in literal.chars() {
+        if "*?[]".contains(c) {
+            pattern.push('[');
+            pattern.push(c);
+            pattern.push(']');
+        } else {
+            pattern.push(c);
+        }
+    }
+}
+
+/// Writes the regex a variable formatted with `format` matches.
+fn variable_regex(format: Option<Format>, pattern: &mut String) {
+    let format = match format {
+        Some(format) => format,
+        None => return pattern.push_str(".*?"),
+    };
+    let width = format.width().unwrap_or(0);
+
+    if format.flags().is_number_aware() {
+        // Zero padding goes between the sign and the digits, so the output is a sign and
+        // digits, at least `width` characters long.
+        let sign = if format.flags().has_plus_sign() {
+            "[+-]"
+        } else {
+            "[+-]?"
+        };
+
+        return match width {
+            0 | 1 => {
+                let _ = write!(pattern, "{}{}", sign, NUMBER);
+            }
+            width if format.flags().has_plus_sign() => {
+                let _ = write!(pattern, "[+-][0-9.]{{{},}}", width - 1);
+            }
+            width => {
+                let _ = write!(
+                    pattern,
+                    "(?:[+-][0-9.]{{{},}}|[0-9.]{{{},}})",
+                    width - 1,
+                    width
+                );
+            }
+        };
+    }
+
+    if !format.flags().has_plus_sign() {
+        // Padding is made of any character, so the width is only a lower bound.
+        let _ = write!(pattern, ".{{{},}}?", width);
+        return;
+    }
+
+    // The fill can't be told apart from the value by length, so it's matched by character.
+    let mut fill = String::new();
+
+    if width > 0 {
+        escape_regex(format.fill_char().encode_utf8(&mut [0; 4]), &mut fill);
+        fill.push('*');
+    }
+
+    let number = ["[+-]", NUMBER].concat();
+    let parts: [&str; 3] = match format.alignment() {
+        Some(Alignment::Left) => ["", &number, &fill],
+        Some(Alignment::Right) => [&fill, &number, ""],
+        Some(Alignment::Center) | None => [&fill, &number, &fill],
+    };
+
+    pattern.extend(parts);
+}
+
+/// Converts `tokens` to a regex. See [`CompiledFormat::to_regex`].
+///
+/// [`CompiledFormat::to_regex`]: crate::CompiledFormat::to_regex
+pub(crate) fn to_regex<'format, I>(tokens: I) -> Result<String, parse::Error<'format>>
+where
+    I: IntoIterator<Item = Token<'format>>,
+{
+    let mut pattern = String::from("^");
+    let mut captured = Vec::new();
+
+    for token in tokens {
+        match token {
+            Token::Literal(literal) => escape_regex(literal, &mut pattern),
+            // Group names have to be unique, so only the first use of a variable is captured.
+            Token::Variable(name, format) if !captured.contains(&name) => {
+                captured.push(name);
+
+                let _ = write!(pattern, "(?P<{}>", name);
+                variable_regex(format, &mut pattern);
+                pattern.push(')');
+            }
+            Token::Variable(_, format) => {
+                pattern.push_str("(?:");
+                variable_regex(format, &mut pattern);
+                pattern.push(')');
+            }
+            Token::Include(name) => return Err(parse::Error::IncludeError(name)),
+        }
+    }
+
+    pattern.push('$');
+
+    Ok(pattern)
+}
+
+/// Converts `tokens` to a glob. See [`CompiledFormat::to_glob`].
+///
+/// [`CompiledFormat::to_glob`]: crate::CompiledFormat::to_glob
+pub(crate) fn to_glob<'format, I>(tokens: I) -> Result<String, parse::Error<'format>>
+where
+    I: IntoIterator<Item = Token<'format>>,
+{
+    let mut pattern = String::new();
+
+    for token in tokens {
+        match token {
+            Token::Literal(literal) => escape_glob(literal, &mut pattern),
+            Token::Variable(_, format) => {
+                let width = format.and_then(|format| format.width()).unwrap_or(0);
+
+                // A padded variable is at least `width` characters long.
+                pattern.extend((0..width).map(|_| '?'));
+
+                if !pattern.ends_with('*') {
+                    pattern.push('*');
+                }
+            }
+            Token::Include(name) => return Err(parse::Error::IncludeError(name)),
+        }
+    }
+
+    Ok(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compile, context::TypedValue, MatchError};
+    use regex::Regex;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_to_regex_matches_output() {
+        let cases = [
+            (
+                "{date}_{host}_{seq:05}.log",
+                r"^(?P<date>.*?)_(?P<host>.*?)_(?P<seq>(?:[+-][0-9.]{4,}|[0-9.]{5,}))\.log$",
+            ),
+            ("{x:+}", r"^(?P<x>[+-][0-9]+(?:\.[0-9]+)?)$"),
+            (
+                "{x:*<+6}|{x}",
+                r"^(?P<x>[+-][0-9]+(?:\.[0-9]+)?\**)\|(?:.*?)$",
+            ),
+            ("{x:>6}", r"^(?P<x>.{6,}?)$"),
+            ("{x:+06}", r"^(?P<x>[+-][0-9.]{5,})$"),
+            ("{x:0}", r"^(?P<x>[+-]?[0-9]+(?:\.[0-9]+)?)$"),
+        ];
+
+        for (format, expected) in cases {
+            assert_eq!(compile(format).unwrap().to_regex().unwrap(), expected);
+        }
+
+        let format = compile("{name.first}-{seq:05}.{ext}").unwrap();
+        let regex = Regex::new(&format.to_regex().unwrap()).unwrap();
+
+        for seq in [-42, 7, 123456] {
+            let context = BTreeMap::from([
+                ("name.first", TypedValue::Str("a.b")),
+                ("seq", TypedValue::Int32(seq)),
+                ("ext", TypedValue::Str("tar.gz")),
+            ]);
+            let formatted = format.format_str(&context).unwrap();
+            let captures = regex.captures(&formatted).unwrap();
+
+            assert_eq!(&captures["name.first"], "a.b");
+            assert_eq!(captures["seq"].parse::<i32>().unwrap(), seq);
+        }
+
+        assert!(!regex.is_match("a-7.log"));
+        assert!(!regex.is_match("a-0000x.log"));
+    }
+
+    #[test]
+    fn test_to_glob() {
+        let format = compile("{date}_{host:>4}[{seq:05}]*.log").unwrap();
+        let glob = format.to_glob().unwrap();
+
+        assert_eq!(glob, "*_????*[[]?????*[]][*].log");
+
+        let pattern = glob::Pattern::new(&glob).unwrap();
+
+        assert!(pattern.matches("2024-01-02_  ab[00042]*.log"));
+        assert!(!pattern.matches("2024-01-02_ab[00042]*.log"));
+        assert!(!pattern.matches("2024-01-02_crab[00042].log"));
+    }
+
+    #[test]
+    fn test_patterns_reject_includes() {
+        let format = compile("{>header}{x}").unwrap();
+
+        assert!(matches!(
+            format.to_regex(),
+            Err(MatchError::IncludeError("header"))
+        ));
+        assert!(matches!(
+            format.to_glob(),
+            Err(MatchError::IncludeError("header"))
+        ));
+    }
+}
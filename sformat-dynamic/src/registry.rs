@@ -0,0 +1,339 @@
+use crate::{
+    compile::{compile_with_limits, CompileError, CompiledFormat},
+    limits::Limits,
+    token::Node,
+};
+use alloc::{
+    borrow::{Cow, ToOwned},
+    collections::BTreeMap,
+    string::String,
+    vec::Vec,
+};
+use core::fmt;
+
+/// Error building a registry.
+#[derive(Debug)]
+pub enum RegistryError {
+    CompileError(String, CompileError),
+    DuplicateTemplateError(String),
+    UnknownTemplateError(String, String),
+    IncludeCycleError(String),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::CompileError(name, err) => {
+                write!(f, "error compiling template ({}): {}", name, err)
+            }
+            RegistryError::DuplicateTemplateError(name) => {
+                write!(f, "template ({}) is registered more than once", name)
+            }
+            RegistryError::UnknownTemplateError(name, include) => write!(
+                f,
+                "template ({}) includes unknown template ({})",
+                name, include
+            ),
+            RegistryError::IncludeCycleError(cycle) => {
+                write!(f, "templates include each other: {}", cycle)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RegistryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RegistryError::CompileError(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// A template with its includes replaced by the included templates.
+#[derive(Debug)]
+struct Template {
+    ast: Vec<Node>,
+    literal_len: usize,
+}
+
+/// A set of named templates that can include each other with `{>name}`.
+///
+/// An included template is rendered with the same context as the template that includes
+/// it. Includes are resolved when the registry is built, so a template that includes an
+/// unknown template, or templates that include each other, are reported then.
+///
+/// ```rust
+/// # #[cfg(feature = "std")] {
+/// use sformat_dynamic::{context, Registry};
+///
+/// let registry = Registry::new([
+///     ("header", "== {title} =="),
+///     ("row", "{name:<8}{score:>4}"),
+///     ("page", "{>header}\n{>row}\n"),
+/// ])
+/// .unwrap();
+/// let context = context! { title: "Scores", name: "Ferris", score: 99 };
+/// let formatted = registry.get("page").unwrap().format_str(&context);
+///
+/// assert_eq!(formatted.unwrap(), "== Scores ==\nFerris    99\n");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Registry {
+    // Every template is a range of this string, so included templates can be spliced
+    // into the templates that include them.
+    source: String,
+    templates: BTreeMap<String, Template>,
+    limits: Limits,
+}
+
+impl Registry {
+    /// Compiles the templates with the default [`Limits`].
+    pub fn new<I, N, S>(templates: I) -> Result<Registry, RegistryError>
+    where
+        I: IntoIterator<Item = (N, S)>,
+        N: Into<String>,
+        S: AsRef<str>,
+    {
+        Registry::with_limits(templates, Limits::new())
+    }
+
+    /// Compiles the templates, checking each one against `limits`. The token limit also
+    /// applies to templates after their includes are replaced.
+    pub fn with_limits<I, N, S>(templates: I, limits: Limits) -> Result<Registry, RegistryError>
+    where
+        I: IntoIterator<Item = (N, S)>,
+        N: Into<String>,
+        S: AsRef<str>,
+    {
+        let mut source = String::new();
+        let spans: Vec<_> = templates
+            .into_iter()
+            .map(|(name, template)| {
+                let start = source.len();
+
+                source.push_str(template.as_ref());
+
+                (name.into(), start..source.len())
+            })
+            .collect();
+
+        let mut compiled = BTreeMap::new();
+
+        for (name, span) in spans {
+            let ast = match compile_with_limits(&source[span], limits) {
+                Ok(format) => format
+                    .tokens()
+                    .map(|token| Node::new(&source, token))
+                    .collect(),
+                Err(err) => return Err(RegistryError::CompileError(name, err)),
+            };
+
+            if compiled.contains_key(&name) {
+                return Err(RegistryError::DuplicateTemplateError(name));
+            }
+
+            compiled.insert(name, ast);
+        }
+
+        let mut expanded = BTreeMap::new();
+
+        for name in compiled.keys() {
+            expand(
+                name,
+                &compiled,
+                &source,
+                limits,
+                &mut expanded,
+                &mut Vec::new(),
+            )?;
+        }
+
+        let templates = expanded
+            .into_iter()
+            .map(|(name, ast)| {
+                let literal_len = Node::literal_len(&ast);
+
+                (name, Template { ast, literal_len })
+            })
+            .collect();
+
+        Ok(Registry {
+            source,
+            templates,
+            limits,
+        })
+    }
+
+    /// Returns the template with the given name.
+    pub fn get(&self, name: &str) -> Option<CompiledFormat<'_>> {
+        self.templates.get(name).map(|template| {
+            CompiledFormat::from_parts(
+                &self.source,
+                Cow::Borrowed(&template.ast),
+                template.literal_len,
+                self.limits,
+            )
+        })
+    }
+
+    /// Returns the names of the templates, in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.templates.keys().map(String::as_str)
+    }
+}
+
+/// Replaces the includes of the template `name`, and those of the templates it includes,
+/// with the included templates.
+///
+/// `stack` holds the templates that are being expanded, to detect cycles.
+fn expand<'a>(
+    name: &'a str,
+    compiled: &'a BTreeMap<String, Vec<Node>>,
+    source: &str,
+    limits: Limits,
+    expanded: &mut BTreeMap<String, Vec<Node>>,
+    stack: &mut Vec<&'a str>,
+) -> Result<(), RegistryError> {
+    if expanded.contains_key(name) {
+        return Ok(());
+    }
+
+    if let Some(start) = stack.iter().position(|template| *template == name) {
+        let mut cycle = stack[start..].to_vec();
+
+        cycle.push(name);
+
+        return Err(RegistryError::IncludeCycleError(cycle.join(" -> ")));
+    }
+
+    stack.push(name);
+
+    let mut ast = Vec::new();
+
+    for node in compiled[name].iter() {
+        match node {
+            Node::Include(span) => {
+                let include = &source[span.clone()];
+                let (include, _) = compiled.get_key_value(include).ok_or_else(|| {
+                    RegistryError::UnknownTemplateError(name.to_owned(), include.to_owned())
+                })?;
+
+                expand(include, compiled, source, limits, expanded, stack)?;
+                ast.extend(expanded[include].iter().cloned());
+            }
+            node => ast.push(node.clone()),
+        }
+
+        if ast.len() > limits.max_tokens() {
+            return Err(RegistryError::CompileError(
+                name.to_owned(),
+                CompileError::TokenLimitError(limits.max_tokens()),
+            ));
+        }
+    }
+
+    stack.pop();
+    expanded.insert(name.to_owned(), ast);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Registry, RegistryError};
+    use crate::{compile, compile::CompileError, context::TypedValue, format, limits::Limits};
+    use std::{collections::BTreeMap, string::String, vec::Vec};
+
+    #[test]
+    fn test_registry_includes() {
+        let registry = Registry::new([
+            ("footer", "-- {name} --"),
+            ("header", "# {title:^10} #"),
+            ("body", "{>header}|{count:03}|{>footer}"),
+            ("page", "{>body}{>body}"),
+        ])
+        .unwrap();
+        let context = BTreeMap::from([
+            ("title", TypedValue::Str("Crabs")),
+            ("count", TypedValue::Uint8(7)),
+            ("name", TypedValue::Str("Ferris")),
+        ]);
+        let body = format!("# {:^10} #|{:03}|-- {} --", "Crabs", 7, "Ferris");
+
+        assert_eq!(
+            registry.get("body").unwrap().format_str(&context).unwrap(),
+            body
+        );
+        assert_eq!(
+            registry.get("page").unwrap().format_str(&context).unwrap(),
+            format!("{}{}", body, body)
+        );
+        assert!(registry.get("missing").is_none());
+        assert_eq!(
+            registry.names().collect::<Vec<_>>(),
+            ["body", "footer", "header", "page"]
+        );
+    }
+
+    #[test]
+    fn test_registry_errors() {
+        assert!(matches!(
+            Registry::new([("page", "{>header}")]),
+            Err(RegistryError::UnknownTemplateError(name, include))
+                if name == "page" && include == "header"
+        ));
+        assert!(matches!(
+            Registry::new([("a", "{>b}"), ("b", "{>c}"), ("c", "{>a}")]),
+            Err(RegistryError::IncludeCycleError(cycle)) if cycle == "a -> b -> c -> a"
+        ));
+        assert!(matches!(
+            Registry::new([("a", "x{>a}")]),
+            Err(RegistryError::IncludeCycleError(cycle)) if cycle == "a -> a"
+        ));
+        assert!(matches!(
+            Registry::new([("a", "{x"), ("b", "")]),
+            Err(RegistryError::CompileError(name, _)) if name == "a"
+        ));
+        assert!(matches!(
+            Registry::new([("a", ""), ("a", "")]),
+            Err(RegistryError::DuplicateTemplateError(name)) if name == "a"
+        ));
+        assert!(matches!(
+            Registry::with_limits(
+                [("a", "{x}{x}"), ("b", "{>a}{>a}{>a}")],
+                Limits::new().with_max_tokens(4)
+            ),
+            Err(RegistryError::CompileError(name, _)) if name == "b"
+        ));
+    }
+
+    #[test]
+    fn test_registry_limits_expanded_tokens_by_default() {
+        // Each template includes the one before it twice, so `t39` would expand to 2^39
+        // tokens.
+        let templates = (0..40).map(|i| match i {
+            0 => (String::from("t0"), String::from("{x}")),
+            i => (format!("t{}", i), format!("{{>t{0}}}{{>t{0}}}", i - 1)),
+        });
+
+        assert!(matches!(
+            Registry::new(templates),
+            Err(RegistryError::CompileError(name, CompileError::TokenLimitError(65535)))
+                if name == "t16"
+        ));
+    }
+
+    #[test]
+    fn test_include_outside_registry() {
+        let format = compile("{>header}").unwrap();
+        let context = BTreeMap::<&str, TypedValue>::new();
+
+        assert!(matches!(
+            format.format_str(&context),
+            Err(format::Error::IncludeError("header"))
+        ));
+    }
+}
@@ -0,0 +1,708 @@
+use crate::{
+    context::{Context, OwnedValue, TypedValue},
+    format, Name,
+};
+use serde::ser::{self, Serialize};
+use std::{collections::HashMap, fmt::Display};
+
+/// Error building a [`SerializeContext`].
+#[derive(Debug, thiserror::Error)]
+pub enum SerializeError {
+    #[error("expected a struct or map at the root")]
+    ExpectedStructOrMap,
+
+    #[error("map key `{0}` contains a `.`, which would be ambiguous with a nested path")]
+    InvalidKey(String),
+
+    #[error("unsupported value at `{0}`: {1}")]
+    UnsupportedValue(String, &'static str),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl ser::Error for SerializeError {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: Display,
+    {
+        SerializeError::Custom(msg.to_string())
+    }
+}
+
+/// A context built from any value that implements `serde::Serialize`.
+///
+/// The value is walked once when the context is created, and every leaf is stored
+/// under its path. Struct fields and map entries are reachable by name, and nested
+/// values through dotted names like `{user.emails.0}`. Enum variants are transparent,
+/// unit variants resolve to the variant's name, and `None` values are left undefined.
+///
+/// ```rust
+/// use sformat_dynamic::{compile, SerializeContext};
+/// use std::collections::BTreeMap;
+///
+/// let value = BTreeMap::from([("name", "Ferris"), ("species", "crab")]);
+/// let context = SerializeContext::new(&value).unwrap();
+/// let formatted = compile("{name} the {species}").unwrap().format_str(&context);
+///
+/// assert_eq!(formatted.unwrap(), "Ferris the crab");
+/// ```
+pub struct SerializeContext {
+    values: HashMap<String, OwnedValue>,
+}
+
+impl SerializeContext {
+    /// Walks a value's `Serialize` implementation. The value has to serialize to a
+    /// struct or map, and map keys can't contain a `.`.
+    pub fn new<T>(value: &T) -> Result<SerializeContext, SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let mut values = HashMap::new();
+
+        value.serialize(LeafSerializer {
+            values: &mut values,
+            path: String::new(),
+        })?;
+
+        Ok(SerializeContext { values })
+    }
+}
+
+impl<'ctxt> Context<'ctxt> for SerializeContext {
+    fn get_variable<'b>(
+        &'ctxt self,
+        name: Name<'b>,
+    ) -> Result<TypedValue<'ctxt>, format::Error<'b>> {
+        self.values.get_variable(name)
+    }
+}
+
+/// Joins a path and a segment with a `.`.
+fn join_path(path: &str, segment: &str) -> String {
+    if path.is_empty() {
+        segment.to_string()
+    } else {
+        format!("{}.{}", path, segment)
+    }
+}
+
+/// Serializes a value, storing each leaf under its path.
+struct LeafSerializer<'a> {
+    values: &'a mut HashMap<String, OwnedValue>,
+    path: String,
+}
+
+impl<'a> LeafSerializer<'a> {
+    fn insert(self, value: OwnedValue) -> Result<(), SerializeError> {
+        if self.path.is_empty() {
+            return Err(SerializeError::ExpectedStructOrMap);
+        }
+
+        self.values.insert(self.path, value);
+
+        Ok(())
+    }
+
+    /// Fails at the root, which has to be a struct or map.
+    fn check_not_root(&self) -> Result<(), SerializeError> {
+        if self.path.is_empty() {
+            Err(SerializeError::ExpectedStructOrMap)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn compound(self) -> Compound<'a> {
+        Compound {
+            values: self.values,
+            path: self.path,
+            index: 0,
+            key: None,
+        }
+    }
+}
+
+macro_rules! serialize_leaf {
+    ($($method:ident($ty:ty) => $variant:ident),* $(,)?) => {
+        $(
+            fn $method(self, v: $ty) -> Result<(), SerializeError> {
+                self.insert(OwnedValue::$variant(v))
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for LeafSerializer<'a> {
+    type Ok = ();
+    type Error = SerializeError;
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    serialize_leaf! {
+        serialize_bool(bool) => Bool,
+        serialize_i8(i8) => Int8,
+        serialize_i16(i16) => Int16,
+        serialize_i32(i32) => Int32,
+        serialize_i64(i64) => Int64,
+        serialize_i128(i128) => Int128,
+        serialize_u8(u8) => Uint8,
+        serialize_u16(u16) => Uint16,
+        serialize_u32(u32) => Uint32,
+        serialize_u64(u64) => Uint64,
+        serialize_u128(u128) => Uint128,
+        serialize_f32(f32) => Float32,
+        serialize_f64(f64) => Float64,
+        serialize_char(char) => Char,
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), SerializeError> {
+        self.insert(OwnedValue::Str(variant.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), SerializeError> {
+        self.insert(OwnedValue::Str(v.to_string()))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), SerializeError> {
+        Err(SerializeError::UnsupportedValue(self.path, "bytes"))
+    }
+
+    fn serialize_none(self) -> Result<(), SerializeError> {
+        self.check_not_root()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), SerializeError> {
+        self.check_not_root()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SerializeError> {
+        self.check_not_root()
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a>, SerializeError> {
+        self.check_not_root()?;
+        Ok(self.compound())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a>, SerializeError> {
+        self.check_not_root()?;
+        Ok(self.compound())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, SerializeError> {
+        self.check_not_root()?;
+        Ok(self.compound())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, SerializeError> {
+        self.check_not_root()?;
+        Ok(self.compound())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a>, SerializeError> {
+        Ok(self.compound())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, SerializeError> {
+        Ok(self.compound())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, SerializeError> {
+        Ok(self.compound())
+    }
+}
+
+/// Serializes the elements of sequences, maps and structs under the parent's path.
+struct Compound<'a> {
+    values: &'a mut HashMap<String, OwnedValue>,
+    path: String,
+
+    // Index of the next element in a sequence.
+    index: usize,
+
+    // Key of the next map value.
+    key: Option<String>,
+}
+
+impl<'a> Compound<'a> {
+    fn serialize_child<T>(&mut self, segment: &str, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        // Segments are joined with `.`, so `{"a.b": 1}` would collide with `{"a": {"b": 1}}`.
+        if segment.contains('.') {
+            return Err(SerializeError::InvalidKey(join_path(&self.path, segment)));
+        }
+
+        value.serialize(LeafSerializer {
+            values: self.values,
+            path: join_path(&self.path, segment),
+        })
+    }
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let index = self.index.to_string();
+
+        self.index += 1;
+        self.serialize_child(&index, value)
+    }
+}
+
+impl<'a> ser::SerializeSeq for Compound<'a> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        Compound::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTuple for Compound<'a> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        Compound::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for Compound<'a> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        Compound::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTupleVariant for Compound<'a> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        Compound::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeMap for Compound<'a> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.key = Some(key.serialize(KeySerializer(&self.path))?);
+
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ser::Error::custom("serialize_value called before serialize_key"))?;
+
+        self.serialize_child(&key, value)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeStruct for Compound<'a> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.serialize_child(key, value)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for Compound<'a> {
+    type Ok = ();
+    type Error = SerializeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.serialize_child(key, value)
+    }
+
+    fn end(self) -> Result<(), SerializeError> {
+        Ok(())
+    }
+}
+
+/// Serializes a map key to a string. Only strings, chars, integers and unit variants
+/// are supported.
+struct KeySerializer<'a>(&'a str);
+
+impl<'a> KeySerializer<'a> {
+    fn unsupported(self) -> SerializeError {
+        SerializeError::UnsupportedValue(self.0.to_string(), "map key that isn't a string")
+    }
+}
+
+macro_rules! serialize_key_to_string {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method(self, v: $ty) -> Result<String, SerializeError> {
+                Ok(v.to_string())
+            }
+        )*
+    };
+}
+
+macro_rules! serialize_key_unsupported {
+    ($($method:ident($($ty:ty),*)),* $(,)?) => {
+        $(
+            fn $method(self, $(_: $ty),*) -> Result<String, SerializeError> {
+                Err(self.unsupported())
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for KeySerializer<'a> {
+    type Ok = String;
+    type Error = SerializeError;
+    type SerializeSeq = ser::Impossible<String, SerializeError>;
+    type SerializeTuple = ser::Impossible<String, SerializeError>;
+    type SerializeTupleStruct = ser::Impossible<String, SerializeError>;
+    type SerializeTupleVariant = ser::Impossible<String, SerializeError>;
+    type SerializeMap = ser::Impossible<String, SerializeError>;
+    type SerializeStruct = ser::Impossible<String, SerializeError>;
+    type SerializeStructVariant = ser::Impossible<String, SerializeError>;
+
+    serialize_key_to_string! {
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_char(char),
+        serialize_str(&str),
+    }
+
+    serialize_key_unsupported! {
+        serialize_bool(bool),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_bytes(&[u8]),
+        serialize_none(),
+        serialize_unit(),
+        serialize_unit_struct(&'static str),
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, SerializeError> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<String, SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(self.unsupported())
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, SerializeError>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(self.unsupported())
+    }
+
+    fn serialize_seq(
+        self,
+        _len: Option<usize>,
+    ) -> Result<ser::Impossible<String, SerializeError>, SerializeError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_tuple(
+        self,
+        _len: usize,
+    ) -> Result<ser::Impossible<String, SerializeError>, SerializeError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<ser::Impossible<String, SerializeError>, SerializeError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<ser::Impossible<String, SerializeError>, SerializeError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_map(
+        self,
+        _len: Option<usize>,
+    ) -> Result<ser::Impossible<String, SerializeError>, SerializeError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<ser::Impossible<String, SerializeError>, SerializeError> {
+        Err(self.unsupported())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<ser::Impossible<String, SerializeError>, SerializeError> {
+        Err(self.unsupported())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SerializeContext, SerializeError};
+    use crate::{compile, format};
+    use serde::Serialize;
+    use std::collections::HashMap;
+
+    #[derive(Serialize)]
+    enum Role {
+        Admin,
+    }
+
+    #[derive(Serialize)]
+    struct Address {
+        city: &'static str,
+        zip: Option<u32>,
+    }
+
+    #[derive(Serialize)]
+    struct User {
+        name: String,
+        age: u8,
+        score: f64,
+        role: Role,
+        nickname: Option<&'static str>,
+        emails: Vec<&'static str>,
+        address: Address,
+        tags: HashMap<u32, &'static str>,
+    }
+
+    fn user() -> User {
+        User {
+            name: String::from("Ferris"),
+            age: 7,
+            score: -1.5,
+            role: Role::Admin,
+            nickname: None,
+            emails: vec!["ferris@example.com", "crab@example.com"],
+            address: Address {
+                city: "Rustville",
+                zip: Some(1234),
+            },
+            tags: HashMap::from([(1, "crab")]),
+        }
+    }
+
+    #[test]
+    fn test_serialize_context() {
+        let context = SerializeContext::new(&user()).unwrap();
+        let formatted = compile(
+            "{name} {age:03} {score:+.2} {role} {emails.1} {address.city} {address.zip} {tags.1}",
+        )
+        .unwrap()
+        .format_str(&context);
+
+        assert_eq!(
+            formatted.unwrap(),
+            "Ferris 007 -1.50 Admin crab@example.com Rustville 1234 crab"
+        );
+    }
+
+    #[test]
+    fn test_serialize_context_none_is_undefined() {
+        let context = SerializeContext::new(&user()).unwrap();
+        let formatted = compile("{nickname}").unwrap().format_str(&context);
+
+        assert!(matches!(
+            formatted,
+            Err(format::Error::VariableNameError("nickname"))
+        ));
+    }
+
+    #[test]
+    fn test_serialize_context_expects_struct_or_map() {
+        assert!(matches!(
+            SerializeContext::new(&128u32),
+            Err(SerializeError::ExpectedStructOrMap)
+        ));
+        assert!(matches!(
+            SerializeContext::new(&HashMap::from([((1, 2), "tuple key")])),
+            Err(SerializeError::UnsupportedValue(..))
+        ));
+
+        for result in [
+            SerializeContext::new(&None::<u32>),
+            SerializeContext::new(&()),
+            SerializeContext::new(&vec![1, 2]),
+            SerializeContext::new(&(1, "two")),
+        ] {
+            assert!(matches!(result, Err(SerializeError::ExpectedStructOrMap)));
+        }
+    }
+
+    #[test]
+    fn test_serialize_context_rejects_dotted_keys() {
+        let value = HashMap::from([("user", HashMap::from([("first.name", "Ferris")]))]);
+
+        assert!(matches!(
+            SerializeContext::new(&value),
+            Err(SerializeError::InvalidKey(key)) if key == "user.first.name"
+        ));
+    }
+}
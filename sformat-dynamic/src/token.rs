@@ -1,45 +1,105 @@
 use crate::{
-    context::Context,
+    context::TypedValue,
+    escape::{self, Escaper},
     format::{self, Format},
     Name,
 };
-use std::io::Write;
+use core::{fmt::Write, ops::Range};
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum Token<'format> {
     Literal(&'format str),
     Variable(Name<'format>, Option<Format>),
+    /// Renders another template of a `Registry`, e.g. `{>header}`.
+    Include(Name<'format>),
+}
+
+/// A token that refers to the format string by byte range, so it can be stored without
+/// borrowing the format string.
+#[derive(Clone, Debug)]
+pub(crate) enum Node {
+    Literal(Range<usize>),
+    Variable(Range<usize>, Option<Format>),
+    Include(Range<usize>),
+}
+
+impl Node {
+    /// Creates a node from a token that was parsed from `source`.
+    pub(crate) fn new(source: &str, token: Token<'_>) -> Node {
+        let span = |part: &str| {
+            let start = part.as_ptr() as usize - source.as_ptr() as usize;
+
+            start..start + part.len()
+        };
+
+        match token {
+            Token::Literal(lit) => Node::Literal(span(lit)),
+            Token::Variable(name, format) => Node::Variable(span(name), format),
+            Token::Include(name) => Node::Include(span(name)),
+        }
+    }
+
+    /// Returns the total length of the literals, in bytes.
+    pub(crate) fn literal_len(nodes: &[Node]) -> usize {
+        nodes
+            .iter()
+            .map(|node| match node {
+                Node::Literal(span) => span.len(),
+                Node::Variable(..) | Node::Include(_) => 0,
+            })
+            .sum()
+    }
+
+    pub(crate) fn token<'format>(&self, source: &'format str) -> Token<'format> {
+        match self {
+            Node::Literal(span) => Token::Literal(&source[span.clone()]),
+            Node::Variable(span, format) => Token::Variable(&source[span.clone()], *format),
+            Node::Include(span) => Token::Include(&source[span.clone()]),
+        }
+    }
 }
 
 impl<'format> Token<'format> {
-    pub(crate) fn write_token<'b, W, C>(
+    /// Writes the token, looking up variables with `get_variable`.
+    pub(crate) fn write_token<'b, W, F>(
         &self,
         write: &mut W,
-        context: &'b C,
+        get_variable: F,
+        escaper: Option<&dyn Escaper>,
     ) -> Result<(), format::Error<'b>>
     where
-        W: Write,
-        C: Context<'b>,
+        W: Write + ?Sized,
+        F: FnOnce(Name<'b>) -> Result<TypedValue<'b>, format::Error<'b>>,
         'format: 'b,
     {
         match self {
-            Token::Literal(lit) => write
-                .write_all(lit.as_bytes())
-                .map_err(format::Error::WriteLiteralError),
-            Token::Variable(name, None) => {
-                let val = context.get_variable(name)?.string_repr(None);
-
-                write
-                    .write_all(val.as_ref().as_bytes())
-                    .map_err(|err| format::Error::WriteVariableError(name, err))
-            }
-            Token::Variable(name, Some(format)) => {
-                let val = context.get_variable(name)?;
+            Token::Literal(lit) => write.write_str(lit).map_err(format::Error::FmtError),
+            // A `Registry` replaces includes with the included template, so any that are
+            // left are from a format that was compiled on its own.
+            Token::Include(name) => Err(format::Error::IncludeError(name)),
+            Token::Variable(name, format) => {
+                let val = get_variable(name)?;
 
-                format
-                    .write_formatted(val, write)
-                    .map_err(|err| format::Error::WriteVariableError(name, err))
+                Token::write_variable(val, format.as_ref(), escaper, write)
             }
         }
     }
+
+    fn write_variable<'b, W>(
+        val: TypedValue<'_>,
+        format: Option<&Format>,
+        escaper: Option<&dyn Escaper>,
+        write: &mut W,
+    ) -> Result<(), format::Error<'b>>
+    where
+        W: Write + ?Sized,
+    {
+        match format {
+            None => escape::write_escaped(val.string_repr(None).as_ref(), escaper, write)
+                .map_err(format::Error::FmtError),
+            Some(format) => format
+                .write_formatted(val, escaper, write)
+                .map_err(format::Error::FmtError),
+        }
+    }
 }
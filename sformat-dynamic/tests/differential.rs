@@ -0,0 +1,55 @@
+//! Compares formatted output against `std::format!` for random specs and values.
+
+mod support;
+
+use proptest::prelude::*;
+use support::{Value, SPECS};
+
+fn value_strategy() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        any::<String>().prop_map(Value::Str),
+        "[a-zß✓-]{0,8}".prop_map(Value::Str),
+        any::<char>().prop_map(Value::Char),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i8>().prop_map(Value::Int8),
+        any::<i64>().prop_map(Value::Int64),
+        any::<i128>().prop_map(Value::Int128),
+        any::<u64>().prop_map(Value::Uint64),
+        any::<u128>().prop_map(Value::Uint128),
+        any::<f32>().prop_map(Value::Float32),
+        any::<f64>().prop_map(Value::Float64),
+        (-1000.0..1000.0f64).prop_map(Value::Float64),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn test_matches_std_format(
+        spec in 0..SPECS.len(),
+        width in 0usize..48,
+        precision in proptest::option::of(0usize..20),
+        value in value_strategy(),
+    ) {
+        prop_assert_eq!(
+            value.dynamic_format(spec, width, precision),
+            value.std_format(spec, width, precision)
+        );
+    }
+
+    #[test]
+    fn test_matches_std_format_near_value_length(
+        spec in 0..SPECS.len(),
+        value in value_strategy(),
+    ) {
+        // Widths around the length of the value (plus a sign) are where the padding
+        // arithmetic used to underflow.
+        let len = value.std_format(spec, 0, None).chars().count();
+
+        for width in len.saturating_sub(2)..len + 2 {
+            prop_assert_eq!(
+                value.dynamic_format(spec, width, None),
+                value.std_format(spec, width, None)
+            );
+        }
+    }
+}
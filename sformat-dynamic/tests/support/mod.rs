@@ -0,0 +1,97 @@
+//! Differential testing against `std::format!`.
+//!
+//! `std::format!` needs its format spec at compile time, so every fill, alignment and flag
+//! combination under test is spelled out in `SPECS`. Width and precision are passed as
+//! arguments, so they can be anything.
+
+use sformat_dynamic::{compile, TypedValue};
+use std::fmt::Display;
+
+macro_rules! specs {
+    ($($spec:literal),* $(,)?) => {
+        /// The fill, alignment and flags of each spec under test.
+        pub const SPECS: &[&str] = &[$($spec),*];
+
+        /// Formats `value` with `std::format!`, using `SPECS[spec]` followed by the width and
+        /// precision.
+        pub fn std_format<T>(spec: usize, width: usize, precision: Option<usize>, value: T) -> String
+        where
+            T: Display,
+        {
+            match (SPECS[spec], precision) {
+                $(
+                    ($spec, None) => format!(concat!("{:", $spec, "w$}"), value, w = width),
+                    ($spec, Some(p)) => {
+                        format!(concat!("{:", $spec, "w$.p$}"), value, w = width, p = p)
+                    }
+                )*
+                _ => unreachable!(),
+            }
+        }
+    };
+}
+
+specs![
+    "", "+", "0", "+0", "<", "<+", "<0", "<+0", "^", "^+", "^0", "^+0", ">", ">+", ">0", ">+0",
+    "*<", "*<+", "*<0", "*<+0", "*^", "*^+", "*^0", "*^+0", "ß>", "ß>+", "ß>0", "ß>+0", "0^",
+    "0^+", "0>", "0^+0",
+];
+
+/// A value to format, which can be turned into both a `TypedValue` and a `Display`.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Str(String),
+    Char(char),
+    Bool(bool),
+    Int8(i8),
+    Int64(i64),
+    Int128(i128),
+    Uint64(u64),
+    Uint128(u128),
+    Float32(f32),
+    Float64(f64),
+}
+
+impl Value {
+    pub fn typed_value(&self) -> TypedValue<'_> {
+        match self {
+            Value::Str(value) => TypedValue::Str(value),
+            Value::Char(value) => TypedValue::Char(*value),
+            Value::Bool(value) => TypedValue::Bool(*value),
+            Value::Int8(value) => TypedValue::Int8(*value),
+            Value::Int64(value) => TypedValue::Int64(*value),
+            Value::Int128(value) => TypedValue::Int128(*value),
+            Value::Uint64(value) => TypedValue::Uint64(*value),
+            Value::Uint128(value) => TypedValue::Uint128(*value),
+            Value::Float32(value) => TypedValue::Float32(*value),
+            Value::Float64(value) => TypedValue::Float64(*value),
+        }
+    }
+
+    pub fn std_format(&self, spec: usize, width: usize, precision: Option<usize>) -> String {
+        match self {
+            Value::Str(value) => std_format(spec, width, precision, value),
+            Value::Char(value) => std_format(spec, width, precision, value),
+            Value::Bool(value) => std_format(spec, width, precision, value),
+            Value::Int8(value) => std_format(spec, width, precision, value),
+            Value::Int64(value) => std_format(spec, width, precision, value),
+            Value::Int128(value) => std_format(spec, width, precision, value),
+            Value::Uint64(value) => std_format(spec, width, precision, value),
+            Value::Uint128(value) => std_format(spec, width, precision, value),
+            Value::Float32(value) => std_format(spec, width, precision, value),
+            Value::Float64(value) => std_format(spec, width, precision, value),
+        }
+    }
+
+    /// Formats the value as the variable `x` with a compiled format string.
+    pub fn dynamic_format(&self, spec: usize, width: usize, precision: Option<usize>) -> String {
+        let precision = precision.map(|p| format!(".{}", p)).unwrap_or_default();
+        let format_str = format!("{{x:{}{}{}}}", SPECS[spec], width, precision);
+        let context = [("x", self.typed_value())];
+
+        compile(&format_str)
+            .unwrap()
+            .format_str(&context[..])
+            .unwrap()
+    }
+}